@@ -0,0 +1,242 @@
+use bevy::{prelude::*, sprite::Anchor, utils::{HashMap, HashSet}};
+use bevy_rapier2d::prelude::*;
+
+use crate::{ClimbComponent, Dead, GameState, Grapple, Health, JumpComponent, Player, PlayerHurt};
+
+/// Size of a single frame in the Woodcutter sheets
+const FRAME_SIZE : Vec2 = Vec2::new(48.0, 48.0);
+/// The Woodcutter stands on the bottom of its frames, this lines the feet up with the bottom of the player collider
+const SPRITE_ANCHOR : Anchor = Anchor::Custom(Vec2::new(0.0, -10.0 / 48.0));
+/// Horizontal speed below which the player counts as standing still
+const RUN_THRESHOLD : f32 = 10.0;
+/// Times per second the player blinks while invulnerable
+const INVULNERABLE_FLASH_RATE : f32 = 10.0;
+/// Opacity of the player in the faded half of a blink
+const INVULNERABLE_FLASH_ALPHA : f32 = 0.3;
+
+/// Plays the Woodcutter animations on the player
+pub struct PlayerAnimationPlugin;
+
+impl Plugin for PlayerAnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<AnimationFinished>()
+            .add_systems(Startup, load_player_animations)
+            .add_systems(Update, (choose_player_animation, face_movement_direction, animate_sprites, flash_invulnerable_player)
+                .chain()
+                .run_if(in_state(GameState::Playing)));
+    }
+}
+
+/// A horizontal strip of equally sized frames
+#[derive(Clone, Copy, Debug)]
+pub struct PlayerAnimationClip {
+    pub path: &'static str,
+    pub frame_count: usize,
+    pub fps: f32,
+    pub looping: bool
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum PlayerAnimation {
+    #[default]
+    Idle,
+    Run,
+    Jump,
+    Climb,
+    Push,
+    Attack,
+    Hurt,
+    Death
+}
+
+impl PlayerAnimation {
+    pub const ALL : [PlayerAnimation; 8] = [
+        PlayerAnimation::Idle,
+        PlayerAnimation::Run,
+        PlayerAnimation::Jump,
+        PlayerAnimation::Climb,
+        PlayerAnimation::Push,
+        PlayerAnimation::Attack,
+        PlayerAnimation::Hurt,
+        PlayerAnimation::Death
+    ];
+
+    pub fn clip(self) -> PlayerAnimationClip {
+        match self {
+            PlayerAnimation::Idle => PlayerAnimationClip { path: "1 Woodcutter/Woodcutter_idle.png", frame_count: 4, fps: 6.0, looping: true },
+            PlayerAnimation::Run => PlayerAnimationClip { path: "1 Woodcutter/Woodcutter_run.png", frame_count: 6, fps: 12.0, looping: true },
+            PlayerAnimation::Jump => PlayerAnimationClip { path: "1 Woodcutter/Woodcutter_jump.png", frame_count: 6, fps: 12.0, looping: false },
+            PlayerAnimation::Climb => PlayerAnimationClip { path: "1 Woodcutter/Woodcutter_climb.png", frame_count: 6, fps: 10.0, looping: true },
+            PlayerAnimation::Push => PlayerAnimationClip { path: "1 Woodcutter/Woodcutter_push.png", frame_count: 6, fps: 10.0, looping: true },
+            PlayerAnimation::Attack => PlayerAnimationClip { path: "1 Woodcutter/Woodcutter_attack1.png", frame_count: 6, fps: 12.0, looping: false },
+            PlayerAnimation::Hurt => PlayerAnimationClip { path: "1 Woodcutter/Woodcutter_hurt.png", frame_count: 3, fps: 10.0, looping: false },
+            PlayerAnimation::Death => PlayerAnimationClip { path: "1 Woodcutter/Woodcutter_death.png", frame_count: 6, fps: 12.0, looping: false }
+        }
+    }
+
+    /// One-shot animations that aren't interrupted by the regular state changes until they finish
+    pub fn is_one_shot(self) -> bool {
+        matches!(self, PlayerAnimation::Hurt | PlayerAnimation::Death | PlayerAnimation::Attack)
+    }
+}
+
+/// Textures and atlas layouts of every `PlayerAnimation`
+#[derive(Resource, Default)]
+pub struct PlayerAnimations {
+    pub sheets: HashMap<PlayerAnimation, (Handle<Image>, Handle<TextureAtlasLayout>)>
+}
+
+#[derive(Component, Default)]
+pub struct AnimationState {
+    pub current: PlayerAnimation,
+    pub frame: usize,
+    /// Seconds spent on the current frame
+    pub frame_time: f32,
+    pub finished: bool,
+    /// Stays on the current frame while set
+    pub paused: bool,
+    /// The sheet of the current animation has to be put on the sprite
+    pub sheet_changed: bool
+}
+
+impl AnimationState {
+    /// Starts an animation from its first frame
+    pub fn play(&mut self, animation: PlayerAnimation) {
+        self.current = animation;
+        self.frame = 0;
+        self.frame_time = 0.0;
+        self.finished = false;
+        self.sheet_changed = true;
+    }
+}
+
+/// Sent when a non-looping animation shows its last frame
+#[derive(Event)]
+pub struct AnimationFinished {
+    pub entity: Entity,
+    pub animation: PlayerAnimation
+}
+
+pub fn load_player_animations(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut layouts: ResMut<Assets<TextureAtlasLayout>>
+) {
+    let mut animations = PlayerAnimations::default();
+
+    for animation in PlayerAnimation::ALL {
+        let clip = animation.clip();
+        let layout = TextureAtlasLayout::from_grid(FRAME_SIZE, clip.frame_count, 1, None, None);
+        animations.sheets.insert(animation, (asset_server.load(clip.path), layouts.add(layout)));
+    }
+
+    commands.insert_resource(animations);
+}
+
+pub fn choose_player_animation(
+    mut commands: Commands,
+    mut player: Query<(Entity, &Velocity, &JumpComponent, &ClimbComponent, Has<Dead>, Option<&mut AnimationState>), With<Player>>,
+    grapple: Query<(), With<Grapple>>,
+    mut player_hurt: EventReader<PlayerHurt>
+) {
+    let hurt: HashSet<Entity> = player_hurt.read().map(|hurt| hurt.player).collect();
+
+    for (entity, velocity, jump_component, climb_component, dead, animation_state) in &mut player {
+        let Some(mut animation_state) = animation_state else {
+            let mut animation_state = AnimationState::default();
+            animation_state.play(PlayerAnimation::Idle);
+            commands.entity(entity).insert(animation_state);
+            continue;
+        };
+
+        // Getting hurt again restarts the animation
+        if hurt.contains(&entity) && !dead {
+            animation_state.play(PlayerAnimation::Hurt);
+            continue;
+        }
+
+        let wanted = if dead {
+            PlayerAnimation::Death
+        } else if climb_component.climbing {
+            PlayerAnimation::Climb
+        } else if !grapple.is_empty() || !(jump_component.on_ground || climb_component.on_ladder_top) {
+            PlayerAnimation::Jump
+        } else if velocity.linvel.x.abs() > RUN_THRESHOLD {
+            PlayerAnimation::Run
+        } else {
+            PlayerAnimation::Idle
+        };
+
+        // Holding still on a ladder pauses the climbing animation
+        animation_state.paused = climb_component.climbing && velocity.linvel.y == 0.0;
+
+        let playing_one_shot = animation_state.current.is_one_shot() && !animation_state.finished;
+        if wanted != animation_state.current && (!playing_one_shot || wanted == PlayerAnimation::Death) {
+            animation_state.play(wanted);
+        }
+    }
+}
+
+pub fn face_movement_direction(mut player: Query<(&Velocity, &mut Sprite), (With<Player>, Without<Dead>)>) {
+    for (velocity, mut sprite) in &mut player {
+        if velocity.linvel.x < -RUN_THRESHOLD {
+            sprite.flip_x = true;
+        } else if velocity.linvel.x > RUN_THRESHOLD {
+            sprite.flip_x = false;
+        }
+    }
+}
+
+pub fn animate_sprites(
+    mut sprites: Query<(Entity, &mut AnimationState, &mut TextureAtlas, &mut Handle<Image>, &mut Sprite)>,
+    animations: Option<Res<PlayerAnimations>>,
+    mut animation_finished: EventWriter<AnimationFinished>,
+    time: Res<Time>
+) {
+    let Some(animations) = animations else {
+        return;
+    };
+
+    for (entity, mut animation_state, mut atlas, mut texture, mut sprite) in &mut sprites {
+        let clip = animation_state.current.clip();
+
+        if animation_state.sheet_changed {
+            if let Some((sheet_texture, sheet_layout)) = animations.sheets.get(&animation_state.current) {
+                *texture = sheet_texture.clone();
+                atlas.layout = sheet_layout.clone();
+                sprite.anchor = SPRITE_ANCHOR;
+            }
+            animation_state.sheet_changed = false;
+        }
+
+        if !animation_state.finished && !animation_state.paused {
+            animation_state.frame_time += time.delta_seconds();
+        }
+
+        while animation_state.frame_time >= 1.0 / clip.fps && !animation_state.finished {
+            animation_state.frame_time -= 1.0 / clip.fps;
+
+            if animation_state.frame + 1 < clip.frame_count {
+                animation_state.frame += 1;
+            } else if clip.looping {
+                animation_state.frame = 0;
+            } else {
+                animation_state.finished = true;
+                animation_finished.send(AnimationFinished { entity, animation: animation_state.current });
+            }
+        }
+
+        atlas.index = animation_state.frame;
+    }
+}
+
+/// Blinks the player while damage is ignored
+pub fn flash_invulnerable_player(mut player: Query<(&Health, &mut Sprite), With<Player>>, time: Res<Time<Fixed>>) {
+    let now = time.elapsed_seconds();
+
+    for (health, mut sprite) in &mut player {
+        let faded = health.invulnerable_until
+            .is_some_and(|until| now < until && ((until - now) * INVULNERABLE_FLASH_RATE * 2.0) as u32 % 2 == 1);
+        sprite.color.set_a(if faded { INVULNERABLE_FLASH_ALPHA } else { 1.0 });
+    }
+}
@@ -1,7 +1,24 @@
 use bevy::{prelude::*, render::camera::ScalingMode};
 use bevy_ecs_ldtk::prelude::*;
 
-use crate::Player;
+use crate::{level_selection_follow_player, GameSet, GameSetsPlugin, Player};
+
+/// Spawns the camera and keeps it inside the currently selected level
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<GameSetsPlugin>() {
+            app.add_plugins(GameSetsPlugin);
+        }
+
+        app.add_systems(Startup, spawn_camera)
+            .add_systems(Update, (start_camera_transition, camera_fit_inside_current_level)
+                .chain()
+                .after(level_selection_follow_player)
+                .in_set(GameSet::Camera));
+    }
+}
 
 #[derive(Default, Component)]
 pub struct CameraTransition {
@@ -19,6 +36,29 @@ pub struct CustomCameraBundle {
 
 const MAX_SCREEN_WIDTH : f32 = 350.0;
 
+pub fn spawn_camera(mut commands: Commands) {
+    commands.spawn(CustomCameraBundle::default());
+}
+
+pub fn start_camera_transition(
+    level_selection: Res<LevelSelection>,
+    mut camera: Query<(&Transform, &mut CameraTransition, &OrthographicProjection)>,
+    time: Res<Time>
+) {
+    // The initial level selection isn't a transition
+    if level_selection.is_changed() && !level_selection.is_added() {
+        if let Ok((camera_transform, mut camera_transition, projection)) = camera.get_single_mut() {
+            camera_transition.is_changing_level = true;
+            camera_transition.begin_position = camera_transform.translation.xy();
+            camera_transition.begin_time = time.elapsed_seconds();
+            if let ScalingMode::Fixed { width, height } = projection.scaling_mode {
+                camera_transition.begin_scale.x = width;
+                camera_transition.begin_scale.y = height;
+            }
+        }
+    }
+}
+
 pub fn camera_fit_inside_current_level(
     mut camera_query: Query<
         (
@@ -43,8 +83,10 @@ pub fn camera_fit_inside_current_level(
     {
         let player_translation = *player_translation;
 
-        let (mut orthographic_projection, mut camera_transform, mut camera_transition) = camera_query.single_mut();
-        let window = window.single();
+        // Nothing to fit without a camera and a window, e.g. in the headless app
+        let (Ok((mut orthographic_projection, mut camera_transform, mut camera_transition)), Ok(window)) = (camera_query.get_single_mut(), window.get_single()) else {
+            return;
+        };
         let aspect_ratio : f32 = window.width() / window.height();
         let mut wanted_camera_position = Vec2::new(0.0, 0.0);
         let mut wanted_scale = Vec2::new(0.0, 0.0);
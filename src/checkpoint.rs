@@ -0,0 +1,130 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{Dead, GameSet, GameSetsPlugin, GameState, Player};
+
+/// Color of a checkpoint the player hasn't activated
+const INACTIVE_CHECKPOINT_COLOR : Color = Color::rgb(0.45, 0.45, 0.5);
+/// Color of the checkpoint the player respawns at
+const ACTIVE_CHECKPOINT_COLOR : Color = Color::rgb(0.3, 0.85, 0.35);
+
+/// Checkpoints that the player respawns at after touching them
+pub struct CheckpointPlugin;
+
+impl Plugin for CheckpointPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<GameSetsPlugin>() {
+            app.add_plugins(GameSetsPlugin);
+        }
+
+        app.register_ldtk_entity::<CheckpointBundle>("Checkpoint")
+            .init_resource::<ActiveCheckpoint>()
+            .add_systems(FixedUpdate, activate_checkpoints.in_set(GameSet::GroundDetection))
+            .add_systems(Update, show_active_checkpoint)
+            .add_systems(OnEnter(GameState::MainMenu), reset_active_checkpoint);
+    }
+}
+
+#[derive(Default, Component)]
+pub struct Checkpoint;
+
+#[derive(Default, Bundle, LdtkEntity)]
+pub struct CheckpointBundle {
+    checkpoint: Checkpoint,
+    #[with(checkpoint_sprite)]
+    sprite_bundle: SpriteBundle,
+    #[with(checkpoint_collider)]
+    collider: Collider,
+    sensor: Sensor
+}
+
+fn checkpoint_sprite(entity_instance: &EntityInstance) -> SpriteBundle {
+    SpriteBundle {
+        sprite: Sprite {
+            color: INACTIVE_CHECKPOINT_COLOR,
+            custom_size: Some(Vec2::new(entity_instance.width as f32, entity_instance.height as f32)),
+            ..default()
+        },
+        ..default()
+    }
+}
+
+fn checkpoint_collider(entity_instance: &EntityInstance) -> Collider {
+    Collider::cuboid(entity_instance.width as f32 / 2.0, entity_instance.height as f32 / 2.0)
+}
+
+/// Where a checkpoint is, kept after its level has been despawned
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct CheckpointLocation {
+    /// Iid of the level the checkpoint is in
+    pub level: String,
+    /// Iid of the checkpoint entity
+    pub iid: String,
+    /// World position the player respawns at
+    pub position: Vec2
+}
+
+/// The last checkpoint the player touched. Stays active when the player walks into other levels until a level is completed,
+/// and is used by every respawn instead of the level's `Spawnpoint` while there is one.
+#[derive(Resource, Clone, PartialEq, Debug, Default)]
+pub struct ActiveCheckpoint(pub Option<CheckpointLocation>);
+
+impl ActiveCheckpoint {
+    /// Position of the active checkpoint if it is in the given level
+    pub fn position_in(&self, level: &LevelIid) -> Option<Vec2> {
+        self.0.as_ref()
+            .filter(|checkpoint| checkpoint.level == *level.get())
+            .map(|checkpoint| checkpoint.position)
+    }
+}
+
+pub fn reset_active_checkpoint(mut active_checkpoint: ResMut<ActiveCheckpoint>) {
+    *active_checkpoint = ActiveCheckpoint::default();
+}
+
+pub fn activate_checkpoints(
+    mut collisions: EventReader<CollisionEvent>,
+    checkpoints: Query<(&EntityIid, &GlobalTransform, &Parent), With<Checkpoint>>,
+    player: Query<(), (With<Player>, Without<Dead>)>,
+    // Checkpoint -> layer -> level
+    layers: Query<&Parent, With<LayerMetadata>>,
+    levels: Query<&LevelIid>,
+    mut active_checkpoint: ResMut<ActiveCheckpoint>
+) {
+    for collision_event in collisions.read() {
+        let CollisionEvent::Started(e1, e2, _) = collision_event else {
+            continue;
+        };
+
+        for (checkpoint_entity, player_entity) in [(e1, e2), (e2, e1)] {
+            let (Ok((iid, transform, parent)), true) = (checkpoints.get(*checkpoint_entity), player.contains(*player_entity)) else {
+                continue;
+            };
+            let Some(level) = layers.get(parent.get()).ok().and_then(|layer| levels.get(layer.get()).ok()) else {
+                continue;
+            };
+
+            if active_checkpoint.0.as_ref().is_some_and(|active| active.iid == iid.as_str()) {
+                continue;
+            }
+
+            active_checkpoint.0 = Some(CheckpointLocation {
+                level: level.get().clone(),
+                iid: iid.as_str().to_string(),
+                position: transform.translation().xy()
+            });
+        }
+    }
+}
+
+pub fn show_active_checkpoint(mut checkpoints: Query<(&EntityIid, &mut Sprite), With<Checkpoint>>, active_checkpoint: Res<ActiveCheckpoint>) {
+    for (iid, mut sprite) in &mut checkpoints {
+        let active = active_checkpoint.0.as_ref().is_some_and(|active| active.iid == iid.as_str());
+        let color = if active { ACTIVE_CHECKPOINT_COLOR } else { INACTIVE_CHECKPOINT_COLOR };
+        if sprite.color != color {
+            sprite.color = color;
+        }
+    }
+}
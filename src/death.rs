@@ -0,0 +1,386 @@
+use bevy::{prelude::*, utils::HashSet};
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{ActiveCheckpoint, CheckpointPlugin, ClimbComponent, DashComponent, GameSet, GameSetsPlugin, GameState, Grapple, JumpComponent, PendingLevelTransfer, Player, Spawnpoint, SpawnpointBundle, SpikesBundle, Teleported};
+
+/// How long the player stays frozen after dying before being respawned
+const DEATH_DURATION : f32 = 0.5;
+/// Health the player starts and respawns with
+pub const PLAYER_MAX_HEALTH : u32 = 3;
+/// How long the player can't be hurt again after taking damage
+const INVULNERABILITY_DURATION : f32 = 1.0;
+/// Speed the player is knocked away from what hurt them with
+const KNOCKBACK_SPEED : f32 = 150.0;
+/// Horizontal input is ignored for this long after being knocked back
+const KNOCKBACK_INPUT_LOCK : f32 = 0.2;
+
+/// Hazards, health, spawnpoints and the death and respawn of the player
+pub struct HazardPlugin;
+
+impl Plugin for HazardPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<GameSetsPlugin>() {
+            app.add_plugins(GameSetsPlugin);
+        }
+        if !app.is_plugin_added::<CheckpointPlugin>() {
+            app.add_plugins(CheckpointPlugin);
+        }
+
+        app.register_ldtk_entity::<SpawnpointBundle>("Spawnpoint")
+            .register_ldtk_int_cell::<SpikesBundle>(4)
+            .add_event::<Damage>()
+            .add_event::<PlayerHurt>()
+            .add_event::<PlayerDied>()
+            .add_event::<PlayerRespawned>()
+            .init_resource::<LevelEntry>()
+            .init_resource::<LevelRules>()
+            .add_systems(FixedUpdate, (add_hazard_contacts, read_level_rules).in_set(GameSet::PhysicsPrep))
+            .add_systems(FixedUpdate, (detect_hazard_contacts, damage_from_hazards, apply_damage, start_death, hold_dead_player, respawn_player)
+                .chain()
+                .in_set(GameSet::GroundDetection))
+            .add_systems(Update, record_level_entry.in_set(GameSet::Camera))
+            .add_systems(OnEnter(GameState::MainMenu), reset_level_entry);
+    }
+}
+
+/// Hurts whatever touches it. Spikes deal 1 damage.
+#[derive(Clone, Copy, Component, Debug)]
+pub struct Hazard {
+    pub damage: u32
+}
+
+impl Default for Hazard {
+    fn default() -> Self {
+        Hazard { damage: 1 }
+    }
+}
+
+#[derive(Clone, Component, Debug)]
+pub struct Health {
+    pub current: u32,
+    pub max: u32,
+    /// Damage is ignored until then
+    pub invulnerable_until: Option<f32>
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Health { current: PLAYER_MAX_HEALTH, max: PLAYER_MAX_HEALTH, invulnerable_until: None }
+    }
+}
+
+impl Health {
+    pub fn invulnerable(&self, now: f32) -> bool {
+        self.invulnerable_until.is_some_and(|until| now < until)
+    }
+}
+
+/// Hazards an entity with `Health` is touching
+#[derive(Clone, Component, Default, Debug)]
+pub struct HazardContacts {
+    pub hazards: HashSet<Entity>
+}
+
+/// Damage dealt to `target` by `source`
+#[derive(Event, Clone, Copy, Debug)]
+pub struct Damage {
+    pub target: Entity,
+    pub source: Entity,
+    pub amount: u32,
+    /// Normalized direction the target is pushed in
+    pub knockback: Vec2
+}
+
+/// Sent when the player loses health without dying
+#[derive(Event)]
+pub struct PlayerHurt {
+    pub player: Entity
+}
+
+/// Rules of the selected level, read from its LDtk fields:
+/// `OneHitKill` (Bool, hazards kill the player right away instead of taking away health, true by default)
+#[derive(Resource, Debug)]
+pub struct LevelRules {
+    /// Level the rules were read from
+    pub level_iid: Option<String>,
+    pub one_hit_kill: bool
+}
+
+impl Default for LevelRules {
+    fn default() -> Self {
+        LevelRules { level_iid: None, one_hit_kill: true }
+    }
+}
+
+/// Sent when the player touches something deadly
+#[derive(Event)]
+pub struct PlayerDied {
+    pub player: Entity
+}
+
+/// Sent after a dead player has been moved back to a respawn position
+#[derive(Event)]
+pub struct PlayerRespawned {
+    pub player: Entity,
+    pub position: Vec2
+}
+
+/// Marks a player that is waiting to be respawned.
+/// While it is present the player ignores input and doesn't move.
+#[derive(Component)]
+pub struct Dead {
+    pub died_at: f32
+}
+
+/// Position at which the player entered the currently selected level.
+/// Used as a respawn position in levels without a `Spawnpoint`.
+#[derive(Default, Resource)]
+pub struct LevelEntry {
+    pub level_selection: Option<LevelSelection>,
+    pub position: Vec2
+}
+
+pub fn reset_level_entry(mut level_entry: ResMut<LevelEntry>) {
+    *level_entry = LevelEntry::default();
+}
+
+pub fn record_level_entry(
+    player: Query<&GlobalTransform, (With<Player>, Without<Dead>)>,
+    level_selection: Res<LevelSelection>,
+    mut level_entry: ResMut<LevelEntry>
+) {
+    if let Ok(player_transform) = player.get_single() {
+        if level_entry.level_selection.as_ref() != Some(&*level_selection) {
+            level_entry.level_selection = Some(level_selection.clone());
+            level_entry.position = player_transform.translation().xy();
+        }
+    }
+}
+
+pub fn read_level_rules(
+    level_selection: Res<LevelSelection>,
+    ldtk_projects: Query<&Handle<LdtkProject>>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+    mut level_rules: ResMut<LevelRules>
+) {
+    let Some(level) = ldtk_projects.get_single().ok()
+        .and_then(|handle| ldtk_project_assets.get(handle))
+        .and_then(|project| project.find_raw_level_by_level_selection(&level_selection)) else {
+        return;
+    };
+
+    if level_rules.level_iid.as_ref() != Some(&level.iid) {
+        *level_rules = LevelRules {
+            level_iid: Some(level.iid.clone()),
+            one_hit_kill: level.get_bool_field("OneHitKill").copied().unwrap_or(true)
+        };
+    }
+}
+
+pub fn add_hazard_contacts(mut commands: Commands, entities: Query<Entity, Added<Health>>) {
+    for entity in &entities {
+        commands.entity(entity).insert(HazardContacts::default());
+    }
+}
+
+pub fn detect_hazard_contacts(
+    mut event: EventReader<CollisionEvent>,
+    hazards: Query<(), With<Hazard>>,
+    mut contacts: Query<&mut HazardContacts>
+) {
+    for event in event.read() {
+        let (entity, entity_2, started) = match event {
+            CollisionEvent::Started(entity, entity_2, _flags) => (*entity, *entity_2, true),
+            CollisionEvent::Stopped(entity, entity_2, _flags) => (*entity, *entity_2, false)
+        };
+
+        for (hazard, other) in [(entity, entity_2), (entity_2, entity)] {
+            if !hazards.contains(hazard) {
+                continue;
+            }
+
+            if let Ok(mut contacts) = contacts.get_mut(other) {
+                if started {
+                    contacts.hazards.insert(hazard);
+                } else {
+                    contacts.hazards.remove(&hazard);
+                }
+            }
+        }
+    }
+}
+
+/// Keeps hurting whatever stays on a hazard, every time its invulnerability runs out
+pub fn damage_from_hazards(
+    targets: Query<(Entity, &HazardContacts, &Health, &Transform), Without<Dead>>,
+    hazards: Query<(&Hazard, &GlobalTransform, &Collider)>,
+    mut damage: EventWriter<Damage>,
+    time: Res<Time>
+) {
+    for (target, contacts, health, transform) in &targets {
+        if health.invulnerable(time.elapsed_seconds()) {
+            continue;
+        }
+
+        let Some((source, hazard, hazard_transform, hazard_collider)) = contacts.hazards.iter()
+            .filter_map(|entity| hazards.get(*entity).ok().map(|(hazard, transform, collider)| (*entity, hazard, transform, collider)))
+            .max_by_key(|(_, hazard, _, _)| hazard.damage) else {
+            continue;
+        };
+
+        // Away from the closest point of the hazard, straight up when inside of it
+        let position = transform.translation.xy();
+        let center = hazard_transform.translation().xy();
+        let half_extents = hazard_collider.as_cuboid().map_or(Vec2::ZERO, |cuboid| cuboid.half_extents());
+        let closest = position.clamp(center - half_extents, center + half_extents);
+        let knockback = (position - closest).try_normalize().unwrap_or(Vec2::Y);
+
+        damage.send(Damage { target, source, amount: hazard.damage, knockback });
+    }
+}
+
+/// Takes away health and knocks the player back, or kills them when it runs out or the level is one-hit-kill
+pub fn apply_damage(
+    mut damage: EventReader<Damage>,
+    mut targets: Query<(&mut Health, &mut Velocity, &mut JumpComponent, &mut ClimbComponent, &mut DashComponent, &mut GravityScale), (With<Player>, Without<Dead>)>,
+    level_rules: Res<LevelRules>,
+    mut player_hurt: EventWriter<PlayerHurt>,
+    mut player_died: EventWriter<PlayerDied>,
+    time: Res<Time>
+) {
+    let now = time.elapsed_seconds();
+
+    for Damage { target, amount, knockback, .. } in damage.read() {
+        let Ok((mut health, mut velocity, mut jump_component, mut climb_component, mut dash_component, mut gravity_scale)) = targets.get_mut(*target) else {
+            continue;
+        };
+        if health.invulnerable(now) {
+            continue;
+        }
+
+        health.current = if level_rules.one_hit_kill { 0 } else { health.current.saturating_sub(*amount) };
+        if health.current == 0 {
+            player_died.send(PlayerDied { player: *target });
+            continue;
+        }
+
+        health.invulnerable_until = Some(now + INVULNERABILITY_DURATION);
+        velocity.linvel = *knockback * KNOCKBACK_SPEED;
+        jump_component.jumping = false;
+        jump_component.input_locked_until = Some(now + KNOCKBACK_INPUT_LOCK);
+        climb_component.climbing = false;
+        climb_component.on_ladder_top = false;
+        dash_component.dashing_until = None;
+        *gravity_scale = GravityScale(1.0);
+
+        player_hurt.send(PlayerHurt { player: *target });
+    }
+}
+
+pub fn start_death(
+    mut commands: Commands,
+    mut player_died: EventReader<PlayerDied>,
+    mut player: Query<(&mut Velocity, &mut GravityScale), (With<Player>, Without<Dead>)>,
+    grapple: Query<Entity, With<Grapple>>,
+    time: Res<Time>
+) {
+    for PlayerDied { player: player_entity } in player_died.read() {
+        if let Ok((mut velocity, mut gravity_scale)) = player.get_mut(*player_entity) {
+            *velocity = Velocity::zero();
+            *gravity_scale = GravityScale(0.0);
+
+            commands.entity(*player_entity).insert(Dead { died_at: time.elapsed_seconds() });
+
+            for grapple_entity in &grapple {
+                commands.entity(grapple_entity).despawn_recursive();
+            }
+        }
+    }
+}
+
+pub fn hold_dead_player(mut player: Query<&mut Velocity, (With<Player>, With<Dead>)>) {
+    for mut velocity in &mut player {
+        *velocity = Velocity::zero();
+    }
+}
+
+pub fn respawn_player(
+    mut commands: Commands,
+    mut player: Query<(Entity, &Dead, &mut Transform, &mut Velocity, &mut JumpComponent, &mut ClimbComponent, &mut DashComponent, &mut GravityScale, &mut Health), With<Player>>,
+    level_selection: Res<LevelSelection>,
+    level_entry: Res<LevelEntry>,
+    active_checkpoint: Res<ActiveCheckpoint>,
+    levels: Query<(&LevelIid, &Children)>,
+    ldtk_projects: Query<&Handle<LdtkProject>>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+    spawnpoint: Query<&GlobalTransform, With<Spawnpoint>>,
+    entity_layer: Query<&Children, With<LayerMetadata>>,
+    time: Res<Time>,
+    mut player_respawned: EventWriter<PlayerRespawned>
+) {
+    for (player_entity, dead, mut transform, mut velocity, mut jump_component, mut climb_component, mut dash_component, mut gravity_scale, mut health) in &mut player {
+        if time.elapsed_seconds() - dead.died_at < DEATH_DURATION {
+            continue;
+        }
+
+        let current_level = ldtk_projects.get_single().ok()
+            .and_then(|handle| ldtk_project_assets.get(handle))
+            .and_then(|project| project.find_raw_level_by_level_selection(&level_selection))
+            .map(|level| LevelIid::new(level.iid.clone()));
+
+        let position = if let Some(checkpoint) = &active_checkpoint.0 {
+            // The checkpoint can be in a level the player has walked out of since
+            if current_level.as_ref().map(LevelIid::get) != Some(&checkpoint.level) {
+                commands.insert_resource(PendingLevelTransfer::new(LevelIid::new(checkpoint.level.clone())));
+            }
+            checkpoint.position
+        } else {
+            match current_level.and_then(|level| find_spawnpoint(&level, &levels, &entity_layer, &spawnpoint)) {
+                Some(position) => position,
+                None => {
+                    warn!("Current level has no spawnpoint, respawning at the level entry position");
+                    level_entry.position
+                }
+            }
+        };
+
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+        *velocity = Velocity::zero();
+        *jump_component = JumpComponent::default();
+        // Dying mid dash, e.g. on spikes, doesn't carry the dash over to the respawn
+        *dash_component = DashComponent::default();
+        climb_component.climbing = false;
+        climb_component.on_ladder_top = false;
+        *gravity_scale = GravityScale(1.0);
+        *health = Health { max: health.max, current: health.max, invulnerable_until: None };
+
+        commands.entity(player_entity).remove::<Dead>().insert(Teleported);
+        player_respawned.send(PlayerRespawned { player: player_entity, position });
+    }
+}
+
+/// Finds the position of the `Spawnpoint` in a spawned level by walking level -> layer -> entity
+pub fn find_spawnpoint(
+    level_iid: &LevelIid,
+    levels: &Query<(&LevelIid, &Children)>,
+    entity_layer: &Query<&Children, With<LayerMetadata>>,
+    spawnpoint: &Query<&GlobalTransform, With<Spawnpoint>>
+) -> Option<Vec2> {
+    for (iid, children) in levels.iter() {
+        if iid == level_iid {
+            for &child in children.iter() {
+                if let Ok(children) = entity_layer.get(child) {
+                    for &child in children.iter() {
+                        if let Ok(transform) = spawnpoint.get(child) {
+                            return Some(transform.translation().xy());
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    None
+}
@@ -0,0 +1,317 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{despawn_screen, find_spawnpoint, spawn_screen, Action, ActiveCheckpoint, ActionInputPlugin, ActionState, CheckpointPlugin, Dead, GameSet, GameSetsPlugin, GameState, PendingLevelTransfer, Player, SaveData, Spawnpoint, Teleported};
+
+/// Goals that finish a level and what happens afterwards
+pub struct GoalPlugin;
+
+impl Plugin for GoalPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<GameSetsPlugin>() {
+            app.add_plugins(GameSetsPlugin);
+        }
+        if !app.is_plugin_added::<ActionInputPlugin>() {
+            app.add_plugins(ActionInputPlugin::default());
+        }
+        if !app.is_plugin_added::<CheckpointPlugin>() {
+            app.add_plugins(CheckpointPlugin);
+        }
+
+        app.register_ldtk_entity::<GoalBundle>("Goal")
+            .add_event::<LevelCompleted>()
+            .init_resource::<LevelTimer>()
+            .init_resource::<LevelCompleteAction>()
+            .add_systems(FixedUpdate, (restart_level_timer, reach_goal, clear_active_checkpoint, follow_up_level_completed).chain().in_set(GameSet::GroundDetection))
+            .add_systems(Update, (results_screen_input, level_select_input).run_if(in_state(GameState::LevelComplete)))
+            .add_systems(FixedUpdate, transfer_player_to_level.in_set(GameSet::Movement))
+            .add_systems(OnEnter(GameState::MainMenu), (despawn_screen::<ResultsScreen>, despawn_screen::<LevelSelectScreen>, cancel_level_transfer));
+    }
+}
+
+#[derive(Default, Component)]
+pub struct Goal;
+
+#[derive(Default, Bundle, LdtkEntity)]
+pub struct GoalBundle {
+    goal: Goal,
+    #[sprite_sheet_bundle]
+    sprite_sheet_bundle: SpriteSheetBundle,
+    #[with(goal_collider_init)]
+    collider: Collider,
+    sensor: Sensor
+}
+
+fn goal_collider_init(_value: &EntityInstance) -> Collider {
+    Collider::cuboid(8.0, 8.0)
+}
+
+/// Sent when the player reaches the goal of a level
+#[derive(Event)]
+pub struct LevelCompleted {
+    pub level: LevelIid,
+    /// Seconds since the player entered the level
+    pub time: f32
+}
+
+/// What happens after a level is completed.
+/// The screens pause the game in `GameState::LevelComplete` until the player picks how to continue.
+#[derive(Default, Resource, Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum LevelCompleteAction {
+    /// Move the player to the spawnpoint of the next level in the LDtk project
+    #[default]
+    NextLevel,
+    /// Show the time taken and continue to the next level once the player confirms
+    ResultsScreen,
+    /// Show the list of unlocked levels and let the player choose one with the menu actions
+    LevelSelect
+}
+
+/// Measures how long the player has been in the current level
+#[derive(Default, Resource)]
+pub struct LevelTimer {
+    pub started_at: f32,
+    /// The goal of the current level has been reached already
+    pub completed: bool
+}
+
+#[derive(Component)]
+pub struct ResultsScreen {
+    pub next_level: Option<LevelIid>
+}
+
+#[derive(Component)]
+pub struct LevelSelectScreen {
+    pub levels: Vec<LevelIid>,
+    pub names: Vec<String>,
+    /// Index of the highlighted level
+    pub selected: usize
+}
+
+pub fn restart_level_timer(
+    level_selection: Res<LevelSelection>,
+    mut level_timer: ResMut<LevelTimer>,
+    time: Res<Time>
+) {
+    if level_selection.is_changed() {
+        level_timer.started_at = time.elapsed_seconds();
+        level_timer.completed = false;
+    }
+}
+
+pub fn reach_goal(
+    mut collisions: EventReader<CollisionEvent>,
+    goals: Query<(), With<Goal>>,
+    player: Query<(), (With<Player>, Without<Dead>)>,
+    level_selection: Res<LevelSelection>,
+    ldtk_projects: Query<&Handle<LdtkProject>>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+    mut level_timer: ResMut<LevelTimer>,
+    mut level_completed: EventWriter<LevelCompleted>,
+    time: Res<Time>
+) {
+    for collision_event in collisions.read() {
+        if let CollisionEvent::Started(e1, e2, _) = collision_event {
+            let reached = (goals.contains(*e1) && player.contains(*e2)) || (goals.contains(*e2) && player.contains(*e1));
+            if !reached || level_timer.completed {
+                continue;
+            }
+
+            let level = ldtk_projects.get_single().ok()
+                .and_then(|handle| ldtk_project_assets.get(handle))
+                .and_then(|project| project.find_raw_level_by_level_selection(&level_selection));
+
+            if let Some(level) = level {
+                level_timer.completed = true;
+                level_completed.send(LevelCompleted {
+                    level: LevelIid::new(level.iid.clone()),
+                    time: time.elapsed_seconds() - level_timer.started_at
+                });
+            }
+        }
+    }
+}
+
+/// Whatever level comes next starts from its spawnpoint
+pub fn clear_active_checkpoint(mut level_completed: EventReader<LevelCompleted>, mut active_checkpoint: ResMut<ActiveCheckpoint>) {
+    if level_completed.read().count() > 0 {
+        active_checkpoint.0 = None;
+    }
+}
+
+pub fn follow_up_level_completed(
+    mut commands: Commands,
+    mut level_completed: EventReader<LevelCompleted>,
+    action: Res<LevelCompleteAction>,
+    ldtk_projects: Query<&Handle<LdtkProject>>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+    save: Option<Res<SaveData>>,
+    mut next_state: ResMut<NextState<GameState>>
+) {
+    for LevelCompleted { level, time } in level_completed.read() {
+        info!("Completed level {level} in {time:.2}s");
+
+        let Some(project) = ldtk_projects.get_single().ok().and_then(|handle| ldtk_project_assets.get(handle)) else {
+            continue;
+        };
+
+        let levels: Vec<LevelIid> = project.iter_raw_levels().map(|level| LevelIid::new(level.iid.clone())).collect();
+        let next_level = levels.iter()
+            .position(|iid| iid == level)
+            .and_then(|index| levels.get(index + 1))
+            .cloned();
+
+        match (*action, next_level) {
+            (LevelCompleteAction::NextLevel, Some(next_level)) => {
+                commands.insert_resource(PendingLevelTransfer::new(next_level));
+            }
+            (LevelCompleteAction::NextLevel | LevelCompleteAction::ResultsScreen, next_level) => {
+                spawn_results_screen(&mut commands, *time, next_level);
+                next_state.set(GameState::LevelComplete);
+            }
+            (LevelCompleteAction::LevelSelect, _) => {
+                // The save may not have recorded the level that was just completed yet
+                let unlocked: Vec<(LevelIid, String)> = project.iter_raw_levels().enumerate()
+                    .filter(|(index, _)| {
+                        save.as_ref().map_or(true, |save| save.is_unlocked(&levels, *index))
+                            || (*index > 0 && levels[index - 1] == *level)
+                    })
+                    .map(|(_, raw_level)| (LevelIid::new(raw_level.iid.clone()), raw_level.identifier.clone()))
+                    .collect();
+                let (levels, names): (Vec<LevelIid>, Vec<String>) = unlocked.into_iter().unzip();
+                let screen = LevelSelectScreen { levels, names, selected: 0 };
+                spawn_screen(&mut commands, &level_select_text(&screen), screen);
+                next_state.set(GameState::LevelComplete);
+            }
+        }
+    }
+}
+
+fn spawn_results_screen(commands: &mut Commands, time: f32, next_level: Option<LevelIid>) {
+    let text = match next_level {
+        Some(_) => format!("Level complete!\nTime: {time:.2}s\nPress Enter or A to continue"),
+        None => format!("Level complete!\nTime: {time:.2}s\nThat was the last level, press Enter or A to return to the menu")
+    };
+    spawn_screen(commands, &text, ResultsScreen { next_level });
+}
+
+fn level_select_text(screen: &LevelSelectScreen) -> String {
+    let mut text = String::from("Choose a level with Up and Down, then press Enter or A:\n");
+    for (index, name) in screen.names.iter().enumerate() {
+        let cursor = if index == screen.selected { ">" } else { " " };
+        text.push_str(&format!("{cursor} {name}\n"));
+    }
+    text
+}
+
+pub fn results_screen_input(
+    mut commands: Commands,
+    actions: Res<ActionState>,
+    screens: Query<(Entity, &ResultsScreen)>,
+    mut next_state: ResMut<NextState<GameState>>
+) {
+    if actions.just_pressed(Action::Confirm) {
+        for (entity, screen) in &screens {
+            match &screen.next_level {
+                Some(next_level) => {
+                    commands.insert_resource(PendingLevelTransfer::new(next_level.clone()));
+                    next_state.set(GameState::Playing);
+                }
+                None => next_state.set(GameState::MainMenu)
+            }
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+pub fn level_select_input(
+    mut commands: Commands,
+    actions: Res<ActionState>,
+    mut screens: Query<(Entity, &mut LevelSelectScreen, &mut Text)>,
+    mut next_state: ResMut<NextState<GameState>>
+) {
+    for (entity, mut screen, mut text) in &mut screens {
+        let level_count = screen.levels.len().max(1);
+        if actions.just_pressed(Action::MenuNext) {
+            screen.selected = (screen.selected + 1) % level_count;
+        }
+        if actions.just_pressed(Action::MenuPrevious) {
+            screen.selected = (screen.selected + level_count - 1) % level_count;
+        }
+        if screen.is_changed() {
+            text.sections[0].value = level_select_text(&screen);
+        }
+
+        if let (true, Some(level)) = (actions.just_pressed(Action::Confirm), screen.levels.get(screen.selected)) {
+            commands.insert_resource(PendingLevelTransfer::new(level.clone()));
+            commands.entity(entity).despawn_recursive();
+            next_state.set(GameState::Playing);
+        }
+    }
+}
+
+pub fn cancel_level_transfer(mut commands: Commands) {
+    commands.remove_resource::<PendingLevelTransfer>();
+}
+
+/// Selects the level of a `PendingLevelTransfer` and moves the player to its spawnpoint once it has spawned,
+/// or to the active checkpoint if that is in the level.
+pub fn transfer_player_to_level(
+    mut commands: Commands,
+    transfer: Option<ResMut<PendingLevelTransfer>>,
+    active_checkpoint: Res<ActiveCheckpoint>,
+    mut level_selection: ResMut<LevelSelection>,
+    mut player: Query<(Entity, &mut Transform, &mut Velocity), With<Player>>,
+    levels: Query<(&LevelIid, &Children)>,
+    level_transforms: Query<(&LevelIid, &GlobalTransform)>,
+    ldtk_projects: Query<&Handle<LdtkProject>>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+    spawnpoint: Query<&GlobalTransform, With<Spawnpoint>>,
+    entity_layer: Query<&Children, With<LayerMetadata>>
+) {
+    let Some(mut transfer) = transfer else {
+        return;
+    };
+
+    // The player is spawned with the first level, so a new world has to keep that level selected until then
+    if player.is_empty() {
+        return;
+    }
+
+    // The player was moved last frame and its GlobalTransform is up to date now,
+    // so following the player can't select the previous level anymore
+    if transfer.player_moved {
+        commands.remove_resource::<PendingLevelTransfer>();
+        return;
+    }
+
+    let new_level_selection = LevelSelection::Iid(transfer.level.clone());
+    if *level_selection != new_level_selection {
+        *level_selection = new_level_selection;
+    }
+
+    // Waits for the level to spawn like the spawnpoint does, so the player doesn't fall through it
+    let level_spawned = levels.iter().any(|(iid, _)| *iid == transfer.level);
+    let checkpoint_position = active_checkpoint.position_in(&transfer.level).filter(|_| level_spawned);
+
+    let position = checkpoint_position.or_else(|| find_spawnpoint(&transfer.level, &levels, &entity_layer, &spawnpoint)).or_else(|| {
+        // Fall back to the middle of the level if it has spawned without a spawnpoint
+        let (_, level_transform) = level_transforms.iter().find(|(iid, _)| **iid == transfer.level)?;
+        let level = ldtk_project_assets.get(ldtk_projects.get_single().ok()?)?.get_raw_level_by_iid(transfer.level.get())?;
+        warn!("Level {} has no spawnpoint, moving the player to its center", level.identifier);
+        Some(level_transform.translation().xy() + Vec2::new(level.px_wid as f32, level.px_hei as f32) / 2.0)
+    });
+
+    if let Some(position) = position {
+        for (player_entity, mut transform, mut velocity) in &mut player {
+            transform.translation.x = position.x;
+            transform.translation.y = position.y;
+            *velocity = Velocity::zero();
+            commands.entity(player_entity).insert(Teleported);
+        }
+
+        transfer.player_moved = true;
+    }
+}
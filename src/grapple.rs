@@ -0,0 +1,355 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::{dynamics::RopeJointBuilder, prelude::*};
+
+use crate::{Action, ActionInputPlugin, Dead, GameSet, GameSetsPlugin, GameState, Player, PlayerActions};
+
+/// How long the rope of a missed grapple stays visible
+const GRAPPLE_MISS_DURATION : f32 = 0.2;
+/// How far before a hook the line of sight ray can hit a wall without the hook counting as hidden
+const HOOK_LINE_OF_SIGHT_TOLERANCE : f32 = 8.0;
+
+/// Lets the player attach a rope to a `Hook` with the grapple action, reel it in and out and swing by moving sideways
+pub struct GrapplePlugin;
+
+impl Plugin for GrapplePlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<GameSetsPlugin>() {
+            app.add_plugins(GameSetsPlugin);
+        }
+        if !app.is_plugin_added::<ActionInputPlugin>() {
+            app.add_plugins(ActionInputPlugin::default());
+        }
+
+        app.register_ldtk_entity::<HookBundle>("Hook")
+            .init_resource::<GrappleSettings>()
+            .add_event::<GrappleMissed>()
+            .add_systems(FixedUpdate, grapple.in_set(GameSet::Input))
+            .add_systems(FixedUpdate, (show_grapple_miss, fade_grapple_miss).in_set(GameSet::Movement))
+            .register_type::<RopeSettings>()
+            .init_resource::<RopeSettings>()
+            .add_systems(FixedUpdate, (reel_grapple, swing_on_grapple, grapple_look_at_player).in_set(GameSet::Movement))
+            .add_systems(OnEnter(GameState::MainMenu), despawn_grapples);
+    }
+}
+
+#[derive(Default, Component)]
+pub struct Hook;
+
+#[derive(Default, Bundle, LdtkEntity)]
+pub struct HookBundle {
+    hook: Hook,
+
+    #[sprite_bundle("hook.png")]
+    sprite_bundle: SpriteBundle,
+}
+
+/// How the grapple picks its target
+#[derive(Resource, Clone, Debug)]
+pub struct GrappleSettings {
+    /// Targets further away from the player than this can't be grappled
+    pub max_range: f32,
+    /// Hooks hidden behind walls can't be grappled
+    pub require_line_of_sight: bool,
+    /// Aiming at a `Grappleable` fixed collider attaches the grapple where the aim hits it
+    pub grapple_surfaces: bool
+}
+
+impl Default for GrappleSettings {
+    fn default() -> Self {
+        GrappleSettings {
+            max_range: 150.0,
+            require_line_of_sight: true,
+            grapple_surfaces: true
+        }
+    }
+}
+
+/// Feel of the rope once the grapple is attached.
+/// Registered for reflection so it can be tweaked live from the inspector.
+#[derive(Resource, Reflect, Clone, Debug)]
+#[reflect(Resource)]
+pub struct RopeSettings {
+    /// The rope can't be reeled in shorter than this
+    pub min_length: f32,
+    /// The rope can't be reeled out longer than this
+    pub max_length: f32,
+    /// Length change per second while reeling
+    pub reel_speed: f32,
+    /// Acceleration applied while pumping the swing by moving sideways
+    pub swing_force: f32
+}
+
+impl Default for RopeSettings {
+    fn default() -> Self {
+        RopeSettings {
+            min_length: 16.0,
+            max_length: 160.0,
+            reel_speed: 60.0,
+            swing_force: 150.0
+        }
+    }
+}
+
+/// Marks a fixed collider the grapple can attach to anywhere, see `GrappleSettings::grapple_surfaces`.
+/// `spawn_wall_collision` adds it to the colliders of `GrappleableWallBundle` tiles.
+#[derive(Default, Component, Copy, Clone)]
+pub struct Grappleable;
+
+/// Sent when the player tries to grapple but there's nothing to attach to
+#[derive(Event)]
+pub struct GrappleMissed {
+    pub from: Vec2,
+    pub towards: Vec2
+}
+
+/// Rope shown briefly after a missed grapple
+#[derive(Component)]
+pub struct GrappleMiss {
+    pub spawned_at: f32
+}
+
+#[derive(Default, Component)]
+pub struct Grapple;
+
+#[derive(Default, Bundle)]
+pub struct GrapppleBundle {
+    grapple: Grapple,
+    sprite_bundle: SpriteBundle,
+    pub rigid_body: RigidBody,
+    pub velocity: Velocity,
+    pub rotation_constraints: LockedAxes,
+    pub gravity_scale: GravityScale,
+    pub friction: Friction,
+}
+
+pub fn grapple(
+    mut commands: Commands,
+    mut player: Query<(Entity, &Transform, &mut Velocity), (With<Player>, Without<Dead>)>,
+    grapple: Query<Entity, With<Grapple>>,
+    actions: Res<PlayerActions>,
+    rapier_context: Res<RapierContext>,
+    hook: Query<&GlobalTransform, With<Hook>>,
+    grappleable: Query<(), With<Grappleable>>,
+    settings: Res<GrappleSettings>,
+    rope_settings: Res<RopeSettings>,
+    mut grapple_missed: EventWriter<GrappleMissed>
+) {
+    // Dead players can't grapple, their grapple is removed in start_death
+    if player.is_empty() {
+        return;
+    }
+
+    if actions.just_pressed(Action::Grapple) {
+        let (player_entity, player_transform, _) = player.single();
+        let player_position = player_transform.translation.xy();
+
+        // There's no aim without a cursor or an aim stick, e.g. in the headless app
+        if let Some(aim_point) = actions.aim_target() {
+            match find_grapple_target(player_position, aim_point, &hook, &grappleable, &rapier_context, &settings) {
+                Some(hit_point) => {
+                    let rope_length = hit_point.distance(player_position).clamp(rope_settings.min_length, rope_settings.max_length);
+                    let joint = RopeJointBuilder::new(rope_length)
+                        .local_anchor1(Vec2::new(0.0, 0.0))
+                        .local_anchor2(Vec2::new(0.0, 0.0)).build();
+
+                    commands.spawn(GrapppleBundle {
+                        sprite_bundle: SpriteBundle {
+                            sprite: Sprite {
+                                color: Color::RED,
+                                custom_size: Some(Vec2::new(50.0, 1.0)),
+                                anchor: bevy::sprite::Anchor::CenterLeft,
+                                ..default()
+                            },
+                            transform: Transform::from_translation(Vec3::new(hit_point.x, hit_point.y, 10.0)),
+                            ..default()
+                        },
+                        rigid_body: RigidBody::Dynamic,
+                        gravity_scale: GravityScale(0.0),
+                        ..default()
+                    }).insert(ImpulseJoint::new(player_entity, joint));
+                }
+                None => {
+                    grapple_missed.send(GrappleMissed {
+                        from: player_position,
+                        towards: aim_point
+                    });
+                }
+            }
+        }
+    }
+
+    if actions.just_released(Action::Grapple) {
+        if let Ok(entity) = grapple.get_single() {
+            commands.entity(entity).despawn();
+
+            let (_, _, mut player_velocity) = player.single_mut();
+            if player_velocity.linvel.y > 0.0 {
+                player_velocity.linvel.y *= 1.5;
+            }
+        }
+    }
+}
+
+/// Picks the point the grapple attaches to when aiming from `from` at `aim`.
+///
+/// Hooks in range are tried from the closest to the aimed point, skipping the ones behind walls
+/// if `require_line_of_sight` is set. Without a usable hook, the aim ray can attach to a `Grappleable` fixed collider.
+pub fn find_grapple_target(
+    from: Vec2,
+    aim: Vec2,
+    hook: &Query<&GlobalTransform, With<Hook>>,
+    grappleable: &Query<(), With<Grappleable>>,
+    rapier_context: &RapierContext,
+    settings: &GrappleSettings
+) -> Option<Vec2> {
+    let mut hooks_in_range: Vec<Vec2> = hook.iter()
+        .map(|transform| transform.translation().xy())
+        .filter(|position| position.distance(from) <= settings.max_range)
+        .collect();
+    hooks_in_range.sort_by(|a, b| a.distance_squared(aim).total_cmp(&b.distance_squared(aim)));
+
+    let visible_hook = hooks_in_range.into_iter().find(|&position| {
+        if !settings.require_line_of_sight {
+            return true;
+        }
+
+        let distance = position.distance(from);
+        if distance <= f32::EPSILON {
+            return true;
+        }
+
+        // Hooks sit on walls, so hitting a wall right next to the hook doesn't block it
+        match rapier_context.cast_ray(from, (position - from) / distance, distance, true, QueryFilter::only_fixed().exclude_sensors()) {
+            Some((_, toi)) => toi >= distance - HOOK_LINE_OF_SIGHT_TOLERANCE,
+            None => true
+        }
+    });
+
+    if visible_hook.is_some() || !settings.grapple_surfaces {
+        return visible_hook;
+    }
+
+    let ray_dir = (aim - from).normalize_or_zero();
+    if ray_dir == Vec2::ZERO {
+        return None;
+    }
+
+    rapier_context.cast_ray(from, ray_dir, settings.max_range, true, QueryFilter::only_fixed().exclude_sensors())
+        .filter(|(entity, _)| grappleable.contains(*entity))
+        .map(|(_, toi)| from + ray_dir * toi)
+}
+
+pub fn show_grapple_miss(
+    mut commands: Commands,
+    mut grapple_missed: EventReader<GrappleMissed>,
+    settings: Res<GrappleSettings>,
+    time: Res<Time>
+) {
+    for GrappleMissed { from, towards } in grapple_missed.read() {
+        let diff = *towards - *from;
+        let length = diff.length().min(settings.max_range);
+
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::GRAY,
+                    custom_size: Some(Vec2::new(length, 1.0)),
+                    anchor: bevy::sprite::Anchor::CenterLeft,
+                    ..default()
+                },
+                transform: Transform::from_translation(from.extend(10.0))
+                    .with_rotation(Quat::from_axis_angle(Vec3::Z, diff.y.atan2(diff.x))),
+                ..default()
+            },
+            GrappleMiss { spawned_at: time.elapsed_seconds() }
+        ));
+    }
+}
+
+pub fn fade_grapple_miss(
+    mut commands: Commands,
+    mut misses: Query<(Entity, &GrappleMiss, &mut Sprite)>,
+    time: Res<Time>
+) {
+    for (entity, miss, mut sprite) in &mut misses {
+        let progress = (time.elapsed_seconds() - miss.spawned_at) / GRAPPLE_MISS_DURATION;
+        if progress >= 1.0 {
+            commands.entity(entity).despawn();
+        } else {
+            sprite.color.set_a(1.0 - progress);
+        }
+    }
+}
+
+/// The player the rope is attached to is gone with the world
+pub fn despawn_grapples(mut commands: Commands, grapples: Query<Entity, Or<(With<Grapple>, With<GrappleMiss>)>>) {
+    for entity in &grapples {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn grapple_look_at_player(
+    player: Query<&Transform, With<Player>>,
+    mut grapple: Query<(&mut Transform, &mut Sprite), (With<Grapple>, Without<Player>)>
+) {
+    if let Ok(player) = player.get_single() {
+        if let Ok((mut grapple, mut sprite)) = grapple.get_single_mut() {
+            let diff = player.translation - grapple.translation;
+            let angle = diff.y.atan2(diff.x);
+            grapple.rotation = Quat::from_axis_angle(Vec3::Z, angle);
+            sprite.custom_size = Some(Vec2::new(diff.length(), 1.0));
+        }
+    }
+}
+
+/// Shortens the rope while reeling in and lengthens it while reeling out
+pub fn reel_grapple(
+    actions: Res<PlayerActions>,
+    mut grapple: Query<&mut ImpulseJoint, With<Grapple>>,
+    settings: Res<RopeSettings>,
+    time: Res<Time>
+) {
+    let reel_in = actions.value(Action::ReelIn);
+    let reel_out = actions.value(Action::ReelOut);
+    let change = (reel_out - reel_in) * settings.reel_speed * time.delta_seconds();
+
+    if change == 0.0 {
+        return;
+    }
+
+    for mut joint in &mut grapple {
+        if let Some(rope_joint) = joint.data.as_rope_mut() {
+            let length = (rope_joint.max_distance() + change).clamp(settings.min_length, settings.max_length);
+            rope_joint.set_max_distance(length);
+        }
+    }
+}
+
+/// Pumps the swing by pushing the player perpendicular to the rope while moving sideways
+pub fn swing_on_grapple(
+    actions: Res<PlayerActions>,
+    mut player: Query<(&Transform, &mut Velocity), (With<Player>, Without<Dead>)>,
+    grapple: Query<&Transform, (With<Grapple>, Without<Player>)>,
+    settings: Res<RopeSettings>,
+    time: Res<Time>
+) {
+    let right = actions.value(Action::MoveRight);
+    let left = actions.value(Action::MoveLeft);
+    let direction = right - left;
+
+    if direction == 0.0 {
+        return;
+    }
+
+    if let (Ok((transform, mut velocity)), Ok(grapple_transform)) = (player.get_single_mut(), grapple.get_single()) {
+        let rope = (grapple_transform.translation - transform.translation).xy().normalize_or_zero();
+        let mut tangent = rope.perp();
+        // perp is counterclockwise, make it point the way the player is pushing
+        if tangent.x * direction < 0.0 {
+            tangent = -tangent;
+        }
+
+        velocity.linvel += tangent * settings.swing_force * time.delta_seconds();
+    }
+}
@@ -0,0 +1,44 @@
+use std::time::Duration;
+
+use bevy::{app::ScheduleRunnerPlugin, input::gamepad::{GamepadAxis, GamepadButton, Gamepads}, prelude::*, render::render_resource::Shader};
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::{ActionInputPlugin, CheckpointPlugin, GameState, GameWorldPlugin, GoalPlugin, GrapplePlugin, HazardPlugin, LevelCollisionPlugin, MovingPlatformPlugin, PlayerPlugin, ReplayPlugin, SpringPlugin, TileMaterialPlugin};
+
+/// Builds an app that simulates the game without a window or a GPU.
+///
+/// Runs the LDtk loading, Rapier physics and the gameplay plugins on top of `MinimalPlugins`.
+/// Nothing reads from input devices, so `ButtonInput<KeyCode>`, `ButtonInput<MouseButton>` and the gamepad
+/// resources are plain resources that tests and bots can press and release themselves.
+/// The input bindings are the defaults and aren't read from or saved to the config file.
+/// There are no menus, the app starts right in `GameState::Playing`.
+/// Replays can be played back by inserting a `ReplayPlayback`, nothing is recorded unless a `ReplayRecorder` is inserted.
+pub fn headless_app(level_selection: LevelSelection) -> App {
+    let mut app = App::new();
+
+    // Inserted before the game plugins initialize the state to Boot
+    app.insert_state(GameState::Playing)
+        .add_plugins(MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(1.0 / 60.0))))
+        .add_plugins((
+            AssetPlugin::default(),
+            HierarchyPlugin,
+            TransformPlugin,
+            // Only registers the image asset and its loaders, the render parts are skipped without a RenderApp
+            ImagePlugin::default_nearest()
+        ))
+        // Normally added by the render and sprite plugins, needed by LDtk and sprite sheet bundles
+        .init_asset::<Shader>()
+        .init_asset::<TextureAtlasLayout>()
+        .init_resource::<ButtonInput<KeyCode>>()
+        .init_resource::<ButtonInput<MouseButton>>()
+        .init_resource::<Gamepads>()
+        .init_resource::<ButtonInput<GamepadButton>>()
+        .init_resource::<Axis<GamepadAxis>>()
+        .add_plugins(ActionInputPlugin { bindings_path: None })
+        .add_plugins(GameWorldPlugin)
+        .add_plugins((PlayerPlugin, GrapplePlugin, LevelCollisionPlugin, CheckpointPlugin, HazardPlugin, GoalPlugin, SpringPlugin, MovingPlatformPlugin, TileMaterialPlugin))
+        .add_plugins(ReplayPlugin::default())
+        .insert_resource(level_selection);
+
+    app
+}
@@ -0,0 +1,457 @@
+use std::{collections::BTreeMap, path::{Path, PathBuf}};
+
+use bevy::{input::{gamepad::{GamepadAxis, GamepadAxisType, GamepadButton, GamepadButtonType, Gamepads}, InputSystem}, prelude::*, window::PrimaryWindow};
+use serde::{Deserialize, Serialize};
+
+use crate::{GrappleSettings, Player};
+
+/// How far an axis has to be pushed for an axis binding to count as pressed
+const AXIS_PRESS_THRESHOLD : f32 = 0.5;
+/// The aim stick is ignored below this length
+const AIM_DEADZONE : f32 = 0.3;
+
+/// Turns keyboard, mouse and gamepad input into `ActionState` using the `InputBindings`,
+/// and latches it into `PlayerActions` once per fixed tick for the gameplay systems
+pub struct ActionInputPlugin {
+    /// Where the bindings are read from and saved to. `None` keeps them in memory only.
+    pub bindings_path: Option<PathBuf>
+}
+
+impl Default for ActionInputPlugin {
+    fn default() -> Self {
+        ActionInputPlugin {
+            bindings_path: dirs::config_dir().map(|dir| dir.join("bevygame").join("bindings.ron"))
+        }
+    }
+}
+
+impl Plugin for ActionInputPlugin {
+    fn build(&self, app: &mut App) {
+        let bindings = match &self.bindings_path {
+            Some(path) => load_input_bindings(path),
+            None => InputBindings::default()
+        };
+
+        app.insert_resource(bindings)
+            .insert_resource(InputBindingsFile(self.bindings_path.clone()))
+            .init_resource::<ActionState>()
+            .init_resource::<LatchedInput>()
+            .init_resource::<PlayerActions>()
+            .add_systems(PreUpdate, (update_action_state, latch_player_input).chain().after(InputSystem))
+            .add_systems(FixedPreUpdate, apply_tick_input)
+            .add_systems(Last, save_input_bindings);
+    }
+}
+
+/// Something the player can do, independent of the device used to do it
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    /// Climbing up ladders
+    MoveUp,
+    /// Climbing down ladders
+    MoveDown,
+    Jump,
+    Grapple,
+    ReelIn,
+    ReelOut,
+    /// A short burst in the held direction
+    Dash,
+    /// Continuing from menus and screens
+    Confirm,
+    /// Moving the selection of a menu up
+    MenuPrevious,
+    /// Moving the selection of a menu down
+    MenuNext,
+    /// Pausing and resuming the game
+    Pause,
+    /// Leaving the game for the main menu while paused
+    Quit,
+    /// Writing the replay of the current run to a file
+    SaveReplay
+}
+
+impl Action {
+    /// New actions go at the end, so the bits of recorded replays keep their meaning
+    pub const ALL : [Action; 15] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::Jump,
+        Action::Grapple,
+        Action::ReelIn,
+        Action::ReelOut,
+        Action::Confirm,
+        Action::Pause,
+        Action::Quit,
+        Action::SaveReplay,
+        Action::Dash,
+        Action::MenuPrevious,
+        Action::MenuNext
+    ];
+
+    /// Bit of the action in `TickInput::buttons`
+    pub fn bit(self) -> u16 {
+        let index = Action::ALL.iter().position(|action| *action == self).unwrap_or_default();
+        1 << index
+    }
+}
+
+// Every action needs its own bit in `TickInput::buttons`
+const _ : () = assert!(Action::ALL.len() <= u16::BITS as usize);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum AxisDirection {
+    Positive,
+    Negative
+}
+
+/// A single physical input that can trigger an `Action`
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    /// A button on any connected gamepad
+    GamepadButton(GamepadButtonType),
+    /// An axis on any connected gamepad pushed past `AXIS_PRESS_THRESHOLD` in the given direction
+    GamepadAxis(GamepadAxisType, AxisDirection)
+}
+
+/// Which inputs trigger which actions.
+/// Changing the resource saves it to the bindings file.
+#[derive(Resource, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct InputBindings {
+    pub actions: BTreeMap<Action, Vec<Binding>>,
+    /// Gamepad stick used to aim the grapple instead of the mouse cursor
+    pub aim_stick: Option<(GamepadAxisType, GamepadAxisType)>
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        let actions = BTreeMap::from([
+            (Action::MoveLeft, vec![
+                Binding::Key(KeyCode::KeyA),
+                Binding::GamepadButton(GamepadButtonType::DPadLeft),
+                Binding::GamepadAxis(GamepadAxisType::LeftStickX, AxisDirection::Negative)
+            ]),
+            (Action::MoveRight, vec![
+                Binding::Key(KeyCode::KeyD),
+                Binding::GamepadButton(GamepadButtonType::DPadRight),
+                Binding::GamepadAxis(GamepadAxisType::LeftStickX, AxisDirection::Positive)
+            ]),
+            (Action::MoveUp, vec![
+                Binding::Key(KeyCode::KeyW),
+                Binding::GamepadButton(GamepadButtonType::DPadUp),
+                Binding::GamepadAxis(GamepadAxisType::LeftStickY, AxisDirection::Positive)
+            ]),
+            (Action::MoveDown, vec![
+                Binding::Key(KeyCode::KeyS),
+                Binding::GamepadButton(GamepadButtonType::DPadDown),
+                Binding::GamepadAxis(GamepadAxisType::LeftStickY, AxisDirection::Negative)
+            ]),
+            (Action::Jump, vec![Binding::Key(KeyCode::Space), Binding::GamepadButton(GamepadButtonType::South)]),
+            (Action::Grapple, vec![Binding::Mouse(MouseButton::Left), Binding::GamepadButton(GamepadButtonType::RightTrigger2)]),
+            (Action::ReelIn, vec![
+                Binding::Key(KeyCode::KeyW),
+                Binding::GamepadButton(GamepadButtonType::DPadUp),
+                Binding::GamepadAxis(GamepadAxisType::LeftStickY, AxisDirection::Positive)
+            ]),
+            (Action::ReelOut, vec![
+                Binding::Key(KeyCode::KeyS),
+                Binding::GamepadButton(GamepadButtonType::DPadDown),
+                Binding::GamepadAxis(GamepadAxisType::LeftStickY, AxisDirection::Negative)
+            ]),
+            (Action::Dash, vec![Binding::Key(KeyCode::ShiftLeft), Binding::GamepadButton(GamepadButtonType::West)]),
+            (Action::Confirm, vec![Binding::Key(KeyCode::Enter), Binding::GamepadButton(GamepadButtonType::South)]),
+            (Action::MenuPrevious, vec![
+                Binding::Key(KeyCode::ArrowUp),
+                Binding::Key(KeyCode::KeyW),
+                Binding::GamepadButton(GamepadButtonType::DPadUp),
+                Binding::GamepadAxis(GamepadAxisType::LeftStickY, AxisDirection::Positive)
+            ]),
+            (Action::MenuNext, vec![
+                Binding::Key(KeyCode::ArrowDown),
+                Binding::Key(KeyCode::KeyS),
+                Binding::GamepadButton(GamepadButtonType::DPadDown),
+                Binding::GamepadAxis(GamepadAxisType::LeftStickY, AxisDirection::Negative)
+            ]),
+            (Action::Pause, vec![Binding::Key(KeyCode::Escape), Binding::GamepadButton(GamepadButtonType::Start)]),
+            (Action::Quit, vec![Binding::Key(KeyCode::KeyQ), Binding::GamepadButton(GamepadButtonType::Select)]),
+            (Action::SaveReplay, vec![Binding::Key(KeyCode::F9)])
+        ]);
+
+        InputBindings {
+            actions,
+            aim_stick: Some((GamepadAxisType::RightStickX, GamepadAxisType::RightStickY))
+        }
+    }
+}
+
+impl InputBindings {
+    pub fn bindings(&self, action: Action) -> &[Binding] {
+        self.actions.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Replaces every binding of an action
+    pub fn rebind(&mut self, action: Action, bindings: Vec<Binding>) {
+        self.actions.insert(action, bindings);
+    }
+}
+
+/// Path the `InputBindings` are saved to
+#[derive(Resource)]
+pub struct InputBindingsFile(pub Option<PathBuf>);
+
+/// The actions held this frame, updated from the devices before `Update`
+#[derive(Resource, Default)]
+pub struct ActionState {
+    buttons: ButtonInput<Action>,
+    /// Normalized direction of the aim stick, if it is pushed past the deadzone
+    pub aim: Option<Vec2>
+}
+
+impl ActionState {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.buttons.pressed(action)
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.buttons.just_pressed(action)
+    }
+
+    pub fn just_released(&self, action: Action) -> bool {
+        self.buttons.just_released(action)
+    }
+
+    /// 1.0 while the action is pressed, 0.0 otherwise
+    pub fn value(&self, action: Action) -> f32 {
+        if self.pressed(action) { 1.0 } else { 0.0 }
+    }
+}
+
+/// The gameplay actions of one fixed tick. This is what replays record.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct TickInput {
+    /// `Action::bit`s of the actions held during the tick
+    pub buttons: u16,
+    /// World position the grapple is aimed at, only set on the tick it is pressed
+    pub aim: Option<Vec2>
+}
+
+impl TickInput {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.buttons & action.bit() != 0
+    }
+}
+
+/// Input collected from the frames since the last fixed tick
+#[derive(Resource, Default)]
+pub struct LatchedInput {
+    /// Actions held at any point since the last tick, so short taps aren't lost between ticks
+    pub buttons: u16,
+    pub aim: Option<Vec2>,
+    /// Input to use for the next tick instead of the devices, e.g. from a replay
+    pub replayed: Option<TickInput>
+}
+
+/// The actions of the current fixed tick, read by the gameplay systems.
+/// "Just pressed" and "just released" refer to the previous tick rather than the previous frame.
+#[derive(Resource, Default)]
+pub struct PlayerActions {
+    buttons: ButtonInput<Action>,
+    pub input: TickInput
+}
+
+impl PlayerActions {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.buttons.pressed(action)
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.buttons.just_pressed(action)
+    }
+
+    pub fn just_released(&self, action: Action) -> bool {
+        self.buttons.just_released(action)
+    }
+
+    /// 1.0 while the action is pressed, 0.0 otherwise
+    pub fn value(&self, action: Action) -> f32 {
+        if self.pressed(action) { 1.0 } else { 0.0 }
+    }
+
+    /// Where the grapple was aimed when it was pressed this tick
+    pub fn aim_target(&self) -> Option<Vec2> {
+        self.input.aim
+    }
+
+    pub fn apply(&mut self, input: TickInput) {
+        self.buttons.clear();
+        for action in Action::ALL {
+            if input.pressed(action) {
+                self.buttons.press(action);
+            } else {
+                self.buttons.release(action);
+            }
+        }
+        self.input = input;
+    }
+}
+
+/// Reads the bindings file, writing the defaults there first if it doesn't exist yet.
+/// Actions missing from an older file get their default bindings.
+/// An unreadable file is logged and the defaults are used without overwriting it.
+pub fn load_input_bindings(path: &Path) -> InputBindings {
+    match std::fs::read_to_string(path) {
+        Ok(file) => match ron::from_str::<InputBindings>(&file) {
+            Ok(mut bindings) => {
+                for (action, default_bindings) in InputBindings::default().actions {
+                    bindings.actions.entry(action).or_insert(default_bindings);
+                }
+                bindings
+            }
+            Err(error) => {
+                error!("Could not parse the input bindings in {}, using the defaults: {error}", path.display());
+                InputBindings::default()
+            }
+        },
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            let bindings = InputBindings::default();
+            write_input_bindings(path, &bindings);
+            bindings
+        }
+        Err(error) => {
+            error!("Could not read the input bindings from {}, using the defaults: {error}", path.display());
+            InputBindings::default()
+        }
+    }
+}
+
+fn write_input_bindings(path: &Path, bindings: &InputBindings) {
+    let result = ron::ser::to_string_pretty(bindings, ron::ser::PrettyConfig::default())
+        .map_err(|error| error.to_string())
+        .and_then(|text| {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir).map_err(|error| error.to_string())?;
+            }
+            std::fs::write(path, text).map_err(|error| error.to_string())
+        });
+
+    if let Err(error) = result {
+        error!("Could not save the input bindings to {}: {error}", path.display());
+    }
+}
+
+pub fn save_input_bindings(bindings: Res<InputBindings>, file: Res<InputBindingsFile>) {
+    // The file was just read, so there's nothing new to save when the resource was added
+    if !bindings.is_changed() || bindings.is_added() {
+        return;
+    }
+
+    if let Some(path) = &file.0 {
+        write_input_bindings(path, &bindings);
+    }
+}
+
+pub fn update_action_state(
+    mut action_state: ResMut<ActionState>,
+    bindings: Res<InputBindings>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>
+) {
+    let axis_value = |axis_type: GamepadAxisType| -> f32 {
+        gamepads.iter()
+            .filter_map(|gamepad| gamepad_axes.get(GamepadAxis::new(gamepad, axis_type)))
+            .max_by(|a, b| a.abs().total_cmp(&b.abs()))
+            .unwrap_or(0.0)
+    };
+
+    // Counting "just pressed" too keeps presses that were released within the same frame
+    let binding_pressed = |binding: &Binding| -> bool {
+        match *binding {
+            Binding::Key(key) => keyboard.pressed(key) || keyboard.just_pressed(key),
+            Binding::Mouse(button) => mouse.pressed(button) || mouse.just_pressed(button),
+            Binding::GamepadButton(button_type) => gamepads.iter().any(|gamepad| {
+                let button = GamepadButton::new(gamepad, button_type);
+                gamepad_buttons.pressed(button) || gamepad_buttons.just_pressed(button)
+            }),
+            Binding::GamepadAxis(axis_type, direction) => match direction {
+                AxisDirection::Positive => axis_value(axis_type) > AXIS_PRESS_THRESHOLD,
+                AxisDirection::Negative => axis_value(axis_type) < -AXIS_PRESS_THRESHOLD
+            }
+        }
+    };
+
+    action_state.buttons.clear();
+    for action in Action::ALL {
+        if bindings.bindings(action).iter().any(binding_pressed) {
+            action_state.buttons.press(action);
+        } else {
+            action_state.buttons.release(action);
+        }
+    }
+
+    action_state.aim = bindings.aim_stick.and_then(|(x, y)| {
+        let stick = Vec2::new(axis_value(x), axis_value(y));
+        (stick.length() > AIM_DEADZONE).then(|| stick.normalize())
+    });
+}
+
+/// Collects the actions and the grapple aim of this frame until the next fixed tick uses them.
+/// The aim stick points away from the player, otherwise the grapple aims at the cursor.
+pub fn latch_player_input(
+    action_state: Res<ActionState>,
+    mut latched: ResMut<LatchedInput>,
+    player: Query<&GlobalTransform, With<Player>>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform)>,
+    grapple_settings: Option<Res<GrappleSettings>>
+) {
+    for action in Action::ALL {
+        if action_state.pressed(action) {
+            latched.buttons |= action.bit();
+        }
+    }
+
+    if action_state.just_pressed(Action::Grapple) {
+        let stick_aim = action_state.aim.zip(player.get_single().ok()).map(|(direction, player_transform)| {
+            let range = grapple_settings.as_ref().map_or(1.0, |settings| settings.max_range);
+            player_transform.translation().xy() + direction * range
+        });
+
+        latched.aim = stick_aim.or_else(|| {
+            let (Ok((camera, camera_transform)), Ok(window)) = (camera.get_single(), window.get_single()) else {
+                return None;
+            };
+            camera.viewport_to_world_2d(camera_transform, window.cursor_position()?)
+        });
+    }
+}
+
+/// Turns the latched input (or the replayed one) into this tick's `PlayerActions`
+pub fn apply_tick_input(
+    action_state: Res<ActionState>,
+    mut latched: ResMut<LatchedInput>,
+    mut player_actions: ResMut<PlayerActions>
+) {
+    let input = latched.replayed.take().unwrap_or_else(|| {
+        let held = Action::ALL.iter()
+            .filter(|action| action_state.pressed(**action))
+            .fold(latched.buttons, |buttons, action| buttons | action.bit());
+        let grapple_pressed = held & Action::Grapple.bit() != 0 && !player_actions.pressed(Action::Grapple);
+
+        TickInput {
+            buttons: held,
+            aim: if grapple_pressed { latched.aim } else { None }
+        }
+    });
+
+    latched.buttons = 0;
+    latched.aim = None;
+    player_actions.apply(input);
+}
@@ -0,0 +1,99 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::GameSet;
+
+/// Draws moving bodies between their last two physics ticks, so motion stays smooth
+/// when the frame rate is higher than `TickRate` or doesn't divide evenly into it.
+///
+/// The physics position is put back into `Transform` before every tick, so gameplay and Rapier never see the drawn one.
+/// Only the translation is interpolated. The headless app doesn't draw anything and leaves it out.
+pub struct InterpolationPlugin;
+
+impl Plugin for InterpolationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PreUpdate, add_transform_interpolation)
+            .add_systems(FixedFirst, restore_physics_transforms)
+            .add_systems(FixedLast, record_physics_transforms)
+            .add_systems(Update, interpolate_transforms.before(GameSet::Camera));
+    }
+}
+
+/// Translations of a body at the last two physics ticks.
+/// Teleports outside the fixed schedule, e.g. from tests or the inspector, are picked up without interpolating.
+/// Teleports during a tick can't be told apart from moving, so they need to add `Teleported`.
+#[derive(Component, Default, Debug)]
+pub struct TransformInterpolation {
+    pub previous: Vec3,
+    pub current: Vec3,
+    /// What was last written into `Transform`. Anything else there was put by a teleport.
+    rendered: Option<Vec3>
+}
+
+impl TransformInterpolation {
+    /// Jumps straight to the translation if something other than the interpolation has moved the body
+    fn follow_teleport(&mut self, translation: Vec3) {
+        if self.rendered != Some(translation) {
+            self.previous = translation;
+            self.current = translation;
+        }
+    }
+}
+
+/// Marks a body moved somewhere else during a fixed tick, e.g. by respawning,
+/// so it is drawn there right away instead of sliding there over the next tick
+#[derive(Component, Default, Clone, Copy, Debug)]
+pub struct Teleported;
+
+pub fn add_transform_interpolation(
+    mut commands: Commands,
+    bodies: Query<(Entity, &RigidBody), (Added<RigidBody>, Without<TransformInterpolation>)>
+) {
+    for (entity, rigid_body) in &bodies {
+        if *rigid_body != RigidBody::Fixed {
+            commands.entity(entity).insert(TransformInterpolation::default());
+        }
+    }
+}
+
+pub fn restore_physics_transforms(mut bodies: Query<(&mut Transform, &mut TransformInterpolation)>) {
+    for (mut transform, mut interpolation) in &mut bodies {
+        interpolation.follow_teleport(transform.translation);
+        if transform.translation != interpolation.current {
+            transform.translation = interpolation.current;
+        }
+        interpolation.rendered = Some(interpolation.current);
+    }
+}
+
+pub fn record_physics_transforms(
+    mut commands: Commands,
+    mut bodies: Query<(&Transform, &mut TransformInterpolation, Has<Teleported>)>,
+    teleported: Query<Entity, With<Teleported>>
+) {
+    for (transform, mut interpolation, teleported) in &mut bodies {
+        interpolation.previous = if teleported { transform.translation } else { interpolation.current };
+        interpolation.current = transform.translation;
+        interpolation.rendered = Some(transform.translation);
+    }
+
+    for entity in &teleported {
+        commands.entity(entity).remove::<Teleported>();
+    }
+}
+
+pub fn interpolate_transforms(
+    mut bodies: Query<(&mut Transform, &mut TransformInterpolation)>,
+    fixed_time: Res<Time<Fixed>>
+) {
+    let fraction = fixed_time.overstep_fraction();
+
+    for (mut transform, mut interpolation) in &mut bodies {
+        interpolation.follow_teleport(transform.translation);
+        let translation = interpolation.previous.lerp(interpolation.current, fraction);
+        if transform.translation != translation {
+            transform.translation = translation;
+        }
+        interpolation.rendered = Some(translation);
+    }
+}
@@ -0,0 +1,208 @@
+pub mod player;
+pub mod misc;
+pub mod camera;
+pub mod wall_collision;
+pub mod death;
+pub mod grapple;
+pub mod headless;
+pub mod goal;
+pub mod animation;
+pub mod tuning;
+pub mod input;
+pub mod state;
+pub mod save;
+pub mod replay;
+pub mod interpolation;
+pub mod spring;
+pub mod moving_platform;
+pub mod material;
+pub mod checkpoint;
+
+use bevy::{app::PluginGroupBuilder, prelude::*};
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+pub use player::*;
+pub use misc::*;
+pub use camera::*;
+pub use wall_collision::*;
+pub use death::*;
+pub use grapple::*;
+pub use headless::*;
+pub use goal::*;
+pub use animation::*;
+pub use tuning::*;
+pub use input::*;
+pub use state::*;
+pub use save::*;
+pub use replay::*;
+pub use interpolation::*;
+pub use spring::*;
+pub use moving_platform::*;
+pub use material::*;
+pub use checkpoint::*;
+
+/// Default of `TickRate`
+pub const TICK_RATE : f64 = 60.0;
+
+/// Fixed ticks per second of the gameplay systems and physics.
+/// Changing it updates the fixed timestep and Rapier's step to match.
+#[derive(Resource, Clone, Copy, PartialEq, Debug)]
+pub struct TickRate(pub f64);
+
+impl Default for TickRate {
+    fn default() -> Self {
+        TickRate(TICK_RATE)
+    }
+}
+
+/// Stages of a frame, run in the order they are declared:
+/// input -> physics prep -> ground detection -> movement -> (physics step) -> camera.
+///
+/// Everything up to `Movement` runs in `FixedUpdate` before Rapier steps, so a run plays out
+/// the same for the same input regardless of the frame rate. `Camera` runs in `Update` every frame,
+/// after `InterpolationPlugin` has placed the bodies between the last two ticks.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GameSet {
+    /// Reacting to discrete player input, e.g. attaching the grapple
+    Input,
+    /// Spawning colliders and sensors that the rest of the frame relies on
+    PhysicsPrep,
+    /// Reading collision events: ground contact and hazards
+    GroundDetection,
+    /// Applying the player controller to velocities
+    Movement,
+    /// Level selection and camera placement based on the final player position, in `Update`
+    Camera
+}
+
+/// Orders the `GameSet`s and runs them only while `Playing`. Every game plugin adds it if it isn't there yet,
+/// so the plugins can be used on their own.
+pub struct GameSetsPlugin;
+
+impl Plugin for GameSetsPlugin {
+    fn build(&self, app: &mut App) {
+        // Doesn't replace a state inserted earlier, e.g. the headless app starting in Playing
+        app.init_state::<GameState>()
+            .configure_sets(FixedUpdate, (
+                GameSet::Input,
+                GameSet::PhysicsPrep,
+                GameSet::GroundDetection,
+                GameSet::Movement
+            ).chain().before(PhysicsSet::SyncBackend).run_if(in_state(GameState::Playing)))
+            .configure_sets(Update, GameSet::Camera.run_if(in_state(GameState::Playing)));
+    }
+}
+
+/// All of the game plugins
+pub struct GamePlugins;
+
+impl PluginGroup for GamePlugins {
+    fn build(self) -> PluginGroupBuilder {
+        PluginGroupBuilder::start::<Self>()
+            .add(PlayerPlugin)
+            .add(GrapplePlugin)
+            .add(CameraPlugin)
+            .add(LevelCollisionPlugin)
+            .add(CheckpointPlugin)
+            .add(HazardPlugin)
+            .add(GoalPlugin)
+            .add(SpringPlugin)
+            .add(MovingPlatformPlugin)
+            .add(TileMaterialPlugin)
+            .add(FootstepDustPlugin)
+            .add(PlayerAnimationPlugin)
+            .add(GameStatePlugin)
+            .add(SavePlugin::default())
+            .add(ReplayPlugin::recording())
+            .add(InterpolationPlugin)
+    }
+}
+
+/// LDtk world and Rapier physics setup shared by the windowed and the headless app.
+/// Doesn't depend on a window or a renderer.
+///
+/// The world is spawned when the game starts `Playing` and despawned when going back to the main menu.
+/// Physics only steps while `Playing`, and the virtual clock with every timer on it stands still while `Paused`.
+pub struct GameWorldPlugin;
+
+impl Plugin for GameWorldPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<GameSetsPlugin>() {
+            app.add_plugins(GameSetsPlugin);
+        }
+
+        app.add_plugins(LdtkPlugin)
+            .insert_resource(LdtkSettings {
+                level_spawn_behavior: LevelSpawnBehavior::UseWorldTranslation { load_level_neighbors: true },
+                ..default()
+            })
+            .insert_resource(Time::<Fixed>::from_hz(TICK_RATE))
+            .add_plugins(RapierPhysicsPlugin::<OneWayPlatformHooks>::pixels_per_meter(100.0).in_fixed_schedule())
+            .insert_resource(RapierConfiguration {
+                timestep_mode: TimestepMode::Fixed { dt: (1.0 / TICK_RATE) as f32, substeps: 1 },
+                ..RapierConfiguration::new(20.4)
+            })
+            .init_resource::<TickRate>()
+            .add_systems(PreUpdate, apply_tick_rate.run_if(resource_changed::<TickRate>))
+            .add_systems(OnEnter(GameState::Playing), (spawn_ldtk_world, resume_physics))
+            .add_systems(OnExit(GameState::Playing), pause_physics)
+            .add_systems(OnEnter(GameState::Paused), pause_time)
+            .add_systems(OnExit(GameState::Paused), resume_time)
+            .add_systems(OnEnter(GameState::MainMenu), despawn_ldtk_world);
+    }
+}
+
+/// Spawns the world unless it is there already, e.g. when resuming from the pause screen
+pub fn spawn_ldtk_world(mut commands: Commands, asset_server: Res<AssetServer>, worlds: Query<(), With<Handle<LdtkProject>>>) {
+    if !worlds.is_empty() {
+        return;
+    }
+
+    commands.spawn(LdtkWorldBundle {
+        ldtk_handle: asset_server.load("gamemap.ldtk"),
+        ..Default::default()
+    });
+}
+
+/// Removes the world with everything in it, including the `Worldly` player.
+/// The next game starts from the first level, where the player is.
+pub fn despawn_ldtk_world(
+    mut commands: Commands,
+    worlds: Query<Entity, With<Handle<LdtkProject>>>,
+    level_selection: Option<ResMut<LevelSelection>>
+) {
+    for entity in &worlds {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    if let Some(mut level_selection) = level_selection {
+        *level_selection = LevelSelection::index(0);
+    }
+}
+
+pub fn apply_tick_rate(
+    tick_rate: Res<TickRate>,
+    mut fixed_time: ResMut<Time<Fixed>>,
+    mut rapier_configuration: ResMut<RapierConfiguration>
+) {
+    fixed_time.set_timestep_hz(tick_rate.0);
+    rapier_configuration.timestep_mode = TimestepMode::Fixed { dt: (1.0 / tick_rate.0) as f32, substeps: 1 };
+}
+
+pub fn pause_physics(mut rapier_configuration: ResMut<RapierConfiguration>) {
+    rapier_configuration.physics_pipeline_active = false;
+}
+
+pub fn resume_physics(mut rapier_configuration: ResMut<RapierConfiguration>) {
+    rapier_configuration.physics_pipeline_active = true;
+}
+
+/// Stops `Time<Virtual>`, and with it the fixed clock the gameplay timers read
+pub fn pause_time(mut time: ResMut<Time<Virtual>>) {
+    time.pause();
+}
+
+pub fn resume_time(mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+}
@@ -1,90 +1,74 @@
-pub mod player;
-pub mod misc;
-pub mod camera;
-pub mod wall_collision;
-
 use bevy::{log::LogPlugin, prelude::*};
 use bevy_ecs_ldtk::prelude::*;
 use bevy_rapier2d::prelude::*;
-use player::*;
-use misc::*;
-use camera::*;
-use wall_collision::spawn_wall_collision;
+use bevy_inspector_egui::quick::ResourceInspectorPlugin;
+use bevygame::{headless_app, GamePlugins, GameState, GameWorldPlugin, Replay, ReplayPlayback, RopeSettings, SavePlugin, TickRate};
+
+/// The headless app has no save to resume from and always starts in the first level
+const FIRST_LEVEL_IID : &str = "0f72e230-b0a0-11ee-851b-03ba2455339d";
 
 fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins.set(ImagePlugin::default_nearest())
+    let args: Vec<String> = std::env::args().collect();
+    let headless = args.iter().any(|arg| arg == "--headless");
+    // Shows a window to tweak the feel of the rope live
+    let inspector = args.iter().any(|arg| arg == "--inspector");
+    let replay = args.iter().position(|arg| arg == "--replay")
+        .map(|index| {
+            let Some(path) = args.get(index + 1) else {
+                eprintln!("--replay needs the path of a replay file");
+                std::process::exit(1);
+            };
+            Replay::load(path.as_ref()).unwrap_or_else(|error| {
+                eprintln!("Could not load the replay {path}: {error}");
+                std::process::exit(1);
+            })
+        });
+    let tick_rate = args.iter().position(|arg| arg == "--tick-rate")
+        .map(|index| match args.get(index + 1).and_then(|rate| rate.parse::<f64>().ok()) {
+            Some(rate) if rate > 0.0 => TickRate(rate),
+            _ => {
+                eprintln!("--tick-rate needs a positive number of ticks per second");
+                std::process::exit(1);
+            }
+        })
+        .unwrap_or_default();
+
+    if headless {
+        let mut app = headless_app(LevelSelection::iid(FIRST_LEVEL_IID));
+        app.insert_resource(tick_rate);
+        if let Some(replay) = replay {
+            app.insert_resource(ReplayPlayback::new(replay, true));
+        }
+        app.run();
+        return;
+    }
+
+    let mut app = App::new();
+    let mut game_plugins = GamePlugins.build();
+    if let Some(replay) = replay {
+        // A replay skips the menus and leaves the save alone
+        app.insert_state(GameState::Playing)
+            .insert_resource(ReplayPlayback::new(replay, false));
+        game_plugins = game_plugins.disable::<SavePlugin>();
+    }
+
+    app.add_plugins(DefaultPlugins.set(ImagePlugin::default_nearest())
             .set(LogPlugin {
             level: bevy::log::Level::INFO,
             ..default()
             })
         )
-        .add_plugins(LdtkPlugin)
-        .insert_resource(LdtkSettings {
-            level_spawn_behavior: LevelSpawnBehavior::UseWorldTranslation { load_level_neighbors: true },
-            ..default()
-        })
-        .add_plugins(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0))
-        .insert_resource(RapierConfiguration::new(20.4))
+        .add_plugins(GameWorldPlugin)
         .add_plugins(RapierDebugRenderPlugin::default())
-        .add_systems(Startup, setup)
-        .add_systems(Update, camera_fit_inside_current_level)
-        .add_systems(Update, level_selection_follow_player)
-        .add_systems(Update, movement)
-        .add_systems(Update, grapple)
-        .add_systems(Update, grapple_look_at_player)
-        //.add_systems(Update, grapple_pull_player)
-        .add_systems(Update, spawn_wall_collision)
-        .add_systems(Update, spawn_ground_sensor)
-        .add_systems(Update, ground_detection)
-        .add_systems(Update, update_on_ground)
-        .add_systems(Update, collide_with_spikes)
-        .insert_resource(LevelSelection::iid("0f72e230-b0a0-11ee-851b-03ba2455339d"))
-        .register_ldtk_entity::<PlayerBundle>("Player")
-        .register_ldtk_entity::<SpawnpointBundle>("Spawnpoint")
-        .register_ldtk_entity::<HookBundle>("Hook")
-        .register_ldtk_entity::<PushPlatformBundle>("PushPlatform")
-        .register_ldtk_int_cell::<WallBundle>(1)
-        .register_ldtk_int_cell::<WallBundle>(2)
-        .register_ldtk_int_cell::<SpikesBundle>(4)
-        //.add_plugins(WorldInspectorPlugin::new())
-        .run();
-}
-
-pub fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
-    commands.spawn(CustomCameraBundle::default());
+        .add_plugins(game_plugins)
+        // The world starts in the first level where the player is, SavePlugin moves the player to the saved level from there
+        .insert_resource(LevelSelection::index(0))
+        .insert_resource(tick_rate);
 
-    commands.spawn(LdtkWorldBundle {
-        ldtk_handle: asset_server.load("gamemap.ldtk"),
-        ..Default::default()
-    });
-}
-
-#[derive(Default, Component)]
-pub struct Hook;
-
-#[derive(Default, Bundle, LdtkEntity)]
-pub struct HookBundle {
-    hook: Hook,
-
-    #[sprite_bundle("hook.png")]
-    sprite_bundle: SpriteBundle,
-}
-
-#[derive(Default, Component)]
-pub struct PushPlatform;
-
-#[derive(Default, Bundle, LdtkEntity)]
-pub struct PushPlatformBundle {
-    hook: PushPlatform,
-
-    #[sprite_bundle("push_platform.png")]
-    sprite_bundle: SpriteBundle,
-
-    #[with(push_platform_collider_init)]
-    collider: Collider
-}
+    if inspector {
+        app.add_plugins(ResourceInspectorPlugin::<RopeSettings>::default());
+        //app.add_plugins(WorldInspectorPlugin::new());
+    }
 
-fn push_platform_collider_init(_value: &EntityInstance) -> Collider {
-        Collider::cuboid(8.0, 8.0)
+    app.run();
 }
@@ -0,0 +1,199 @@
+use bevy::{prelude::*, sprite::Anchor, utils::HashMap};
+use bevy_rapier2d::prelude::*;
+
+use crate::{movement, Dead, GameSet, GameSetsPlugin, GameState, JumpComponent};
+
+/// Distance walked on the ground between two footsteps
+const FOOTSTEP_STRIDE : f32 = 20.0;
+/// Frame rate of the dust animations
+const DUST_FPS : f32 = 15.0;
+
+/// Physics materials of the wall tiles and the footsteps of the player walking on them
+pub struct TileMaterialPlugin;
+
+impl Plugin for TileMaterialPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<GameSetsPlugin>() {
+            app.add_plugins(GameSetsPlugin);
+        }
+
+        app.init_resource::<TileMaterials>()
+            .add_event::<Footstep>()
+            .add_systems(FixedUpdate, (add_footsteps.in_set(GameSet::PhysicsPrep), emit_footsteps.after(movement).in_set(GameSet::Movement)));
+    }
+}
+
+/// Dust kicked up by a footstep on a material, the small and large one are the `Walk_dust` and `Run_dust` sheets
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum DustEffect {
+    #[default]
+    None,
+    Small,
+    Large
+}
+
+impl DustEffect {
+    /// Sheet, frame size and frame count
+    fn sheet(self) -> Option<(&'static str, Vec2, usize)> {
+        match self {
+            DustEffect::None => None,
+            DustEffect::Small => Some(("4 Effects/Walk_dust_6x24.png", Vec2::new(24.0, 6.0), 6)),
+            DustEffect::Large => Some(("4 Effects/Run_dust_8x38.png", Vec2::new(38.0, 8.0), 6))
+        }
+    }
+}
+
+/// Physics material of a wall collider, also telling what the player's feet do on it.
+///
+/// The player's collider has no friction, so `friction` isn't given to Rapier and only acts through `grip` and `speed_factor`:
+/// below 1 it makes the player speed up and slow down that much slower, above 1 it divides the player's run speed instead.
+#[derive(Clone, Copy, PartialEq, Debug, Component)]
+pub struct TileMaterial {
+    /// Tiles are only merged into one collider with tiles of the same material
+    pub name: &'static str,
+    pub friction: f32,
+    pub restitution: f32,
+    pub dust: DustEffect
+}
+
+impl TileMaterial {
+    pub const DIRT : TileMaterial = TileMaterial { name: "dirt", friction: 1.0, restitution: 0.0, dust: DustEffect::Large };
+    pub const STONE : TileMaterial = TileMaterial { name: "stone", friction: 1.0, restitution: 0.0, dust: DustEffect::Small };
+    pub const ICE : TileMaterial = TileMaterial { name: "ice", friction: 0.05, restitution: 0.0, dust: DustEffect::None };
+    pub const STICKY : TileMaterial = TileMaterial { name: "sticky", friction: 2.0, restitution: 0.0, dust: DustEffect::None };
+
+    /// Fraction of the usual acceleration the player has on the material
+    pub fn grip(&self) -> f32 {
+        self.friction.clamp(0.0, 1.0)
+    }
+
+    /// Fraction of the usual run speed the player has on the material
+    pub fn speed_factor(&self) -> f32 {
+        1.0 / self.friction.max(1.0)
+    }
+}
+
+impl Default for TileMaterial {
+    fn default() -> Self {
+        TileMaterial::DIRT
+    }
+}
+
+/// Material of the wall tiles of every `Walls` IntGrid value. Values without one are dirt.
+#[derive(Resource, Clone, Debug)]
+pub struct TileMaterials(pub HashMap<i32, TileMaterial>);
+
+impl Default for TileMaterials {
+    fn default() -> Self {
+        TileMaterials([
+            (1, TileMaterial::DIRT),
+            (2, TileMaterial::STONE),
+            (6, TileMaterial::ICE),
+            (7, TileMaterial::STICKY)
+        ].into_iter().collect())
+    }
+}
+
+impl TileMaterials {
+    pub fn get(&self, value: i32) -> TileMaterial {
+        self.0.get(&value).copied().unwrap_or_default()
+    }
+}
+
+/// Sent every `FOOTSTEP_STRIDE` pixels an entity walks on the ground
+#[derive(Event, Clone, Copy, Debug)]
+pub struct Footstep {
+    pub entity: Entity,
+    /// Where the feet touch the ground
+    pub position: Vec2,
+    pub material: TileMaterial
+}
+
+/// Distance walked on the ground since the last footstep
+#[derive(Clone, Component, Default, Debug)]
+pub struct FootstepTracker {
+    pub distance: f32
+}
+
+pub fn add_footsteps(mut commands: Commands, walkers: Query<Entity, Added<JumpComponent>>) {
+    for entity in &walkers {
+        commands.entity(entity).insert(FootstepTracker::default());
+    }
+}
+
+pub fn emit_footsteps(
+    mut walkers: Query<(Entity, &JumpComponent, &Transform, &Collider, &Velocity, &mut FootstepTracker), Without<Dead>>,
+    mut footsteps: EventWriter<Footstep>,
+    time: Res<Time>
+) {
+    for (entity, jump_component, transform, collider, velocity, mut tracker) in &mut walkers {
+        let Some(material) = jump_component.ground_material.filter(|_| jump_component.on_ground) else {
+            tracker.distance = 0.0;
+            continue;
+        };
+
+        tracker.distance += velocity.linvel.x.abs() * time.delta_seconds();
+        if tracker.distance >= FOOTSTEP_STRIDE {
+            tracker.distance -= FOOTSTEP_STRIDE;
+            let feet = transform.translation.y - collider.as_cuboid().map_or(0.0, |cuboid| cuboid.half_extents().y);
+            footsteps.send(Footstep { entity, position: Vec2::new(transform.translation.x, feet), material });
+        }
+    }
+}
+
+/// Dust kicked up by footsteps, only for the windowed game
+pub struct FootstepDustPlugin;
+
+impl Plugin for FootstepDustPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (spawn_footstep_dust, animate_dust).chain().run_if(in_state(GameState::Playing)));
+    }
+}
+
+/// Plays once and despawns
+#[derive(Component, Default)]
+pub struct DustPuff {
+    pub frame_count: usize,
+    /// Seconds since the puff was spawned
+    pub age: f32
+}
+
+pub fn spawn_footstep_dust(
+    mut commands: Commands,
+    mut footsteps: EventReader<Footstep>,
+    asset_server: Res<AssetServer>,
+    mut layouts: ResMut<Assets<TextureAtlasLayout>>
+) {
+    for footstep in footsteps.read() {
+        let Some((path, frame_size, frame_count)) = footstep.material.dust.sheet() else {
+            continue;
+        };
+
+        commands.spawn((
+            SpriteSheetBundle {
+                texture: asset_server.load(path),
+                atlas: TextureAtlas {
+                    layout: layouts.add(TextureAtlasLayout::from_grid(frame_size, frame_count, 1, None, None)),
+                    index: 0
+                },
+                sprite: Sprite { anchor: Anchor::BottomCenter, ..default() },
+                transform: Transform::from_translation(footstep.position.extend(5.0)),
+                ..default()
+            },
+            DustPuff { frame_count, age: 0.0 }
+        ));
+    }
+}
+
+pub fn animate_dust(mut commands: Commands, mut puffs: Query<(Entity, &mut DustPuff, &mut TextureAtlas)>, time: Res<Time>) {
+    for (entity, mut puff, mut atlas) in &mut puffs {
+        puff.age += time.delta_seconds();
+
+        let frame = (puff.age * DUST_FPS) as usize;
+        if frame >= puff.frame_count {
+            commands.entity(entity).despawn();
+        } else {
+            atlas.index = frame;
+        }
+    }
+}
@@ -1,5 +1,8 @@
 use bevy::prelude::*;
 use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::Grappleable;
 
 #[derive(Default, Component)]
 pub struct Wall;
@@ -9,6 +12,13 @@ pub struct WallBundle {
     wall: Wall
 }
 
+/// Wall the grapple can attach to anywhere, see `GrappleSettings::grapple_surfaces`
+#[derive(Default, Bundle, LdtkIntCell)]
+pub struct GrappleableWallBundle {
+    wall: Wall,
+    grappleable: Grappleable
+}
+
 #[derive(Default, Component, Copy, Clone)]
 pub struct Spikes;
 
@@ -17,4 +27,19 @@ pub struct SpikesBundle {
     spikes: Spikes
 }
 
+#[derive(Default, Component, Copy, Clone)]
+pub struct Ladder;
+
+#[derive(Default, Bundle, LdtkIntCell)]
+pub struct LadderBundle {
+    ladder: Ladder
+}
+
+/// Can be jumped through from below and dropped through with down + jump
+#[derive(Default, Component, Copy, Clone)]
+pub struct OneWayPlatform;
 
+#[derive(Default, Bundle, LdtkIntCell)]
+pub struct OneWayPlatformBundle {
+    one_way_platform: OneWayPlatform
+}
@@ -0,0 +1,260 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{dash, movement, Dead, GameSet, GameSetsPlugin, GroundSensor, JumpComponent};
+
+/// Speed of platforms without a `speed` field
+const DEFAULT_PLATFORM_SPEED : f32 = 40.0;
+/// Wait at each point of platforms without a `wait` field
+const DEFAULT_PLATFORM_WAIT : f32 = 0.5;
+/// How close a platform has to get to a point to count as there
+const ARRIVAL_TOLERANCE : f32 = 0.01;
+
+/// Kinematic platforms moving along a path, carrying whatever stands on them
+pub struct MovingPlatformPlugin;
+
+impl Plugin for MovingPlatformPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<GameSetsPlugin>() {
+            app.add_plugins(GameSetsPlugin);
+        }
+
+        app.register_ldtk_entity::<MovingPlatformBundle>("MovingPlatform")
+            .add_systems(FixedUpdate, (resolve_platform_paths, add_platform_riders, move_platforms).chain().in_set(GameSet::PhysicsPrep))
+            .add_systems(FixedUpdate, (leave_platform_velocity.before(movement), carry_platform_riders.after(dash)).in_set(GameSet::Movement));
+    }
+}
+
+/// Moves through `waypoints` at `speed`, stopping for `wait` seconds at each of them.
+///
+/// Read from the fields of the LDtk entity:
+/// `path` (Point array, the points after the platform's own position),
+/// `speed` (Float, 40 by default),
+/// `ping_pong` (Bool, goes back along the path after the last point instead of straight to the first, false by default) and
+/// `wait` (Float, 0.5 by default).
+#[derive(Clone, Component, Debug)]
+pub struct MovingPlatform {
+    /// Translations in the platform's parent space, starting with where the platform is placed
+    pub waypoints: Vec<Vec2>,
+    pub speed: f32,
+    pub ping_pong: bool,
+    pub wait: f32,
+    /// Index of the waypoint the platform is moving to
+    pub target: usize,
+    /// Direction along the path while ping-ponging
+    pub forward: bool,
+    pub waiting_until: Option<f32>
+}
+
+impl Default for MovingPlatform {
+    fn default() -> Self {
+        MovingPlatform {
+            waypoints: Vec::new(),
+            speed: DEFAULT_PLATFORM_SPEED,
+            ping_pong: false,
+            wait: DEFAULT_PLATFORM_WAIT,
+            target: 0,
+            forward: true,
+            waiting_until: None
+        }
+    }
+}
+
+impl From<&EntityInstance> for MovingPlatform {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        let default = MovingPlatform::default();
+
+        MovingPlatform {
+            speed: entity_instance.get_float_field("speed").copied().unwrap_or(default.speed),
+            ping_pong: entity_instance.get_bool_field("ping_pong").copied().unwrap_or(default.ping_pong),
+            wait: entity_instance.get_float_field("wait").copied().unwrap_or(default.wait),
+            ..default
+        }
+    }
+}
+
+impl MovingPlatform {
+    pub fn new(waypoints: Vec<Vec2>, speed: f32, ping_pong: bool, wait: f32) -> Self {
+        MovingPlatform { waypoints, speed, ping_pong, wait, ..default() }
+    }
+
+    /// Picks the waypoint after the current target
+    pub fn advance(&mut self) {
+        let last = self.waypoints.len().saturating_sub(1);
+        if last == 0 {
+            return;
+        }
+
+        if !self.ping_pong {
+            self.target = (self.target + 1) % self.waypoints.len();
+            return;
+        }
+
+        if (self.forward && self.target == last) || (!self.forward && self.target == 0) {
+            self.forward = !self.forward;
+        }
+        self.target = if self.forward { self.target + 1 } else { self.target - 1 };
+    }
+}
+
+/// The `path` field in LDtk grid coordinates, turned into `MovingPlatform::waypoints` once the platform is spawned
+#[derive(Clone, Component, Default, Debug)]
+pub struct MovingPlatformPath {
+    pub start: IVec2,
+    pub points: Vec<IVec2>
+}
+
+impl From<&EntityInstance> for MovingPlatformPath {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        MovingPlatformPath {
+            start: entity_instance.grid,
+            points: entity_instance.get_points_field("path").map_or_else(|_| Vec::new(), |points| points.to_vec())
+        }
+    }
+}
+
+/// Velocity of the platform under the entity that was added to it in the last tick
+#[derive(Clone, Component, Default, Debug)]
+pub struct PlatformRider {
+    pub carried: Vec2
+}
+
+#[derive(Default, Bundle, LdtkEntity)]
+pub struct MovingPlatformBundle {
+    #[from_entity_instance]
+    platform: MovingPlatform,
+    #[from_entity_instance]
+    path: MovingPlatformPath,
+    #[with(moving_platform_sprite)]
+    sprite_bundle: SpriteBundle,
+    #[with(moving_platform_collider)]
+    collider: Collider,
+    #[with(moving_platform_rigid_body)]
+    rigid_body: RigidBody,
+    velocity: Velocity
+}
+
+fn moving_platform_sprite(entity_instance: &EntityInstance) -> SpriteBundle {
+    SpriteBundle {
+        sprite: Sprite {
+            color: Color::rgb(0.45, 0.3, 0.2),
+            custom_size: Some(Vec2::new(entity_instance.width as f32, entity_instance.height as f32)),
+            ..default()
+        },
+        ..default()
+    }
+}
+
+fn moving_platform_collider(entity_instance: &EntityInstance) -> Collider {
+    Collider::cuboid(entity_instance.width as f32 / 2.0, entity_instance.height as f32 / 2.0)
+}
+
+fn moving_platform_rigid_body(_value: &EntityInstance) -> RigidBody {
+    RigidBody::KinematicVelocityBased
+}
+
+/// Converts the grid points of the path into translations next to the platform, using the grid size of its layer
+pub fn resolve_platform_paths(
+    mut commands: Commands,
+    mut platforms: Query<(Entity, &mut MovingPlatform, &MovingPlatformPath, &Transform, &Parent)>,
+    layers: Query<&LayerMetadata>
+) {
+    for (entity, mut platform, path, transform, parent) in &mut platforms {
+        let Ok(layer) = layers.get(parent.get()) else {
+            continue;
+        };
+
+        let start = transform.translation.xy();
+        let grid_size = layer.grid_size as f32;
+        platform.waypoints = std::iter::once(start)
+            .chain(path.points.iter().map(|point| {
+                // LDtk's y axis points down
+                let offset = *point - path.start;
+                start + Vec2::new(offset.x as f32, -offset.y as f32) * grid_size
+            }))
+            .collect();
+        platform.target = 0;
+
+        commands.entity(entity).remove::<MovingPlatformPath>();
+    }
+}
+
+pub fn add_platform_riders(mut commands: Commands, riders: Query<Entity, Added<JumpComponent>>) {
+    for entity in &riders {
+        commands.entity(entity).insert(PlatformRider::default());
+    }
+}
+
+pub fn move_platforms(mut platforms: Query<(&Transform, &mut Velocity, &mut MovingPlatform)>, time: Res<Time>) {
+    let now = time.elapsed_seconds();
+    let delta = time.delta_seconds();
+
+    for (transform, mut velocity, mut platform) in &mut platforms {
+        if platform.waypoints.len() < 2 || delta <= 0.0 || platform.waiting_until.is_some_and(|until| now < until) {
+            velocity.linvel = Vec2::ZERO;
+            continue;
+        }
+        platform.waiting_until = None;
+
+        let position = transform.translation.xy();
+        if position.distance(platform.waypoints[platform.target]) <= ARRIVAL_TOLERANCE {
+            platform.advance();
+            if platform.wait > 0.0 {
+                platform.waiting_until = Some(now + platform.wait);
+                velocity.linvel = Vec2::ZERO;
+                continue;
+            }
+        }
+
+        // Slow down on the last step so the platform stops right on the point
+        let to_target = platform.waypoints[platform.target] - position;
+        velocity.linvel = if to_target.length() <= platform.speed * delta {
+            to_target / delta
+        } else {
+            to_target.normalize() * platform.speed
+        };
+    }
+}
+
+/// Platform that the ground sensor of the entity is on, if any
+fn platform_under(
+    rider: Entity,
+    ground_sensors: &Query<&GroundSensor>,
+    platforms: &Query<&Velocity, (With<MovingPlatform>, Without<PlatformRider>)>
+) -> Option<Vec2> {
+    ground_sensors.iter()
+        .filter(|sensor| sensor.ground_detection_entity == rider)
+        .flat_map(|sensor| sensor.intersecting_ground_entities.iter())
+        .find_map(|entity| platforms.get(*entity).ok())
+        .map(|velocity| velocity.linvel)
+}
+
+/// Takes the carried velocity back out while the rider is still on the platform, so the controller works relative to it.
+/// A rider that has left the platform keeps it as momentum.
+pub fn leave_platform_velocity(
+    mut riders: Query<(Entity, &mut Velocity, &mut PlatformRider), Without<Dead>>,
+    ground_sensors: Query<&GroundSensor>,
+    platforms: Query<&Velocity, (With<MovingPlatform>, Without<PlatformRider>)>
+) {
+    for (entity, mut velocity, mut rider) in &mut riders {
+        if platform_under(entity, &ground_sensors, &platforms).is_some() {
+            velocity.linvel -= rider.carried;
+        }
+        rider.carried = Vec2::ZERO;
+    }
+}
+
+/// Moves riders along with the platform they stand on, also while hanging on the grapple
+pub fn carry_platform_riders(
+    mut riders: Query<(Entity, &mut Velocity, &mut PlatformRider), Without<Dead>>,
+    ground_sensors: Query<&GroundSensor>,
+    platforms: Query<&Velocity, (With<MovingPlatform>, Without<PlatformRider>)>
+) {
+    for (entity, mut velocity, mut rider) in &mut riders {
+        if let Some(platform_velocity) = platform_under(entity, &ground_sensors, &platforms) {
+            rider.carried = platform_velocity;
+            velocity.linvel += platform_velocity;
+        }
+    }
+}
@@ -1,10 +1,47 @@
-use bevy::{prelude::*, render::camera::ScalingMode, utils::HashSet, window::PrimaryWindow};
+use bevy::{prelude::*, utils::HashSet};
 use bevy_ecs_ldtk::prelude::*;
-use bevy_rapier2d::{dynamics::RopeJointBuilder, prelude::*};
-
-use crate::{CameraTransition, Hook, PushPlatform, Spikes};
+use bevy_rapier2d::prelude::*;
+
+use crate::{Action, ActionInputPlugin, Dead, GameSet, GameSetsPlugin, Grapple, Health, Ladder, MovementTuning, OneWayPlatform, PlayerActions, Spring, TileMaterial, TuningPlugin};
+
+/// Default of `MovementTuning::jump_grace_period`
+pub const JUMP_GRACE_PERIOD : f32 = 0.1;
+/// Default of `MovementTuning::climb_speed`
+pub const CLIMB_SPEED : f32 = 50.0;
+/// Ticks per second the acceleration and deceleration fractions of `MovementTuning` are given for
+const ACCELERATION_REFERENCE_RATE : f32 = 60.0;
+/// How far the feet can be below the top of a ladder for the player to count as standing on it
+const LADDER_TOP_TOLERANCE : f32 = 2.0;
+/// How far the feet can be below the top of a one-way platform for the player to count as standing on it
+pub const ONE_WAY_TOP_TOLERANCE : f32 = 2.0;
+/// How long down + jump lets the player pass through a one-way platform they stand on.
+/// Once their feet are below its top it lets them through anyway until they have left it.
+const DROP_THROUGH_DURATION : f32 = 0.25;
+
+/// Player controller: spawning, ground detection, movement and following the player across levels
+pub struct PlayerPlugin;
+
+impl Plugin for PlayerPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<GameSetsPlugin>() {
+            app.add_plugins(GameSetsPlugin);
+        }
+        if !app.is_plugin_added::<ActionInputPlugin>() {
+            app.add_plugins(ActionInputPlugin::default());
+        }
+        if !app.is_plugin_added::<TuningPlugin>() {
+            app.add_plugins(TuningPlugin);
+        }
 
-const JUMP_GRACE_PERIOD : f32 = 0.1;
+        app.register_ldtk_entity::<PlayerBundle>("Player")
+            .add_systems(FixedUpdate, (spawn_ground_sensor, spawn_wall_sensors).in_set(GameSet::PhysicsPrep))
+            .add_systems(FixedUpdate, (ground_detection, update_on_ground, wall_detection, update_wall_contact, ladder_detection).chain().in_set(GameSet::GroundDetection))
+            .add_systems(FixedUpdate, (movement, dash).chain().in_set(GameSet::Movement))
+            .add_systems(Update, level_selection_follow_player
+                .run_if(not(resource_exists::<PendingLevelTransfer>))
+                .in_set(GameSet::Camera));
+    }
+}
 
 #[derive(Clone, Default, Bundle, LdtkIntCell)]
 pub struct ColliderBundle {
@@ -25,7 +62,88 @@ pub struct JumpComponent {
     pub last_tried_to_jump: Option<f32>,
     pub jumping: bool,
     pub last_time_jumped: Option<f32>,
-    pub falling: bool
+    pub falling: bool,
+    /// Wall the player touches in the air or on the ground
+    pub wall_contact: WallContact,
+    /// Last time the player was against a wall in the air, for wall jumps within the grace period
+    pub last_on_wall: Option<f32>,
+    /// Which wall that was
+    pub last_wall_contact: WallContact,
+    /// Horizontal input is ignored until then after a wall jump
+    pub input_locked_until: Option<f32>,
+    /// Standing only on one-way platforms, which down + jump drops through
+    pub on_one_way_platform: bool,
+    /// One-way platforms let the player fall through until then
+    pub dropping_through_until: Option<f32>,
+    /// Material of the wall the player stands on, none on anything else
+    pub ground_material: Option<TileMaterial>
+}
+
+impl JumpComponent {
+    pub fn dropping_through(&self, now: f32) -> bool {
+        self.dropping_through_until.is_some_and(|until| now < until)
+    }
+}
+
+/// Side of the player a wall is on
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum WallContact {
+    #[default]
+    None,
+    Left,
+    Right
+}
+
+impl WallContact {
+    /// -1 for a wall on the left, 1 on the right and 0 without one
+    pub fn direction(self) -> f32 {
+        match self {
+            WallContact::None => 0.0,
+            WallContact::Left => -1.0,
+            WallContact::Right => 1.0
+        }
+    }
+}
+
+#[derive(Clone, Default, Component)]
+pub struct ClimbComponent {
+    /// Ladder volumes the player currently overlaps
+    pub intersecting_ladders: HashSet<Entity>,
+    pub climbing: bool,
+    /// Standing on the top of a ladder, which holds the player up like the ground does
+    pub on_ladder_top: bool
+}
+
+#[derive(Clone, Component, Debug)]
+pub struct DashComponent {
+    /// A dash can be used, refreshed by landing or attaching the grapple
+    pub available: bool,
+    /// End of the dash in progress
+    pub dashing_until: Option<f32>,
+    /// Normalized direction of the current or last dash
+    pub direction: Vec2,
+    /// No new dash can start before then
+    pub cooldown_until: Option<f32>,
+    /// Horizontal direction the player last moved in, for dashes without a held direction
+    pub facing: f32
+}
+
+impl Default for DashComponent {
+    fn default() -> Self {
+        DashComponent {
+            available: true,
+            dashing_until: None,
+            direction: Vec2::X,
+            cooldown_until: None,
+            facing: 1.0
+        }
+    }
+}
+
+impl DashComponent {
+    pub fn dashing(&self) -> bool {
+        self.dashing_until.is_some()
+    }
 }
 
 #[derive(Default, Bundle, LdtkEntity)]
@@ -40,6 +158,9 @@ pub struct PlayerBundle {
     #[from_entity_instance]
     collider_bundle: ColliderBundle,
     jump_component: JumpComponent,
+    climb_component: ClimbComponent,
+    dash_component: DashComponent,
+    health: Health,
     #[worldly]
     worldy: Worldly
 }
@@ -53,28 +174,36 @@ pub struct SpawnpointBundle {
     global_transform: GlobalTransform
 }
 
+/// Present while the player is being moved to another level.
+/// The level selection doesn't follow the player in the meantime, so it doesn't switch back to the level they are leaving.
+#[derive(Resource)]
+pub struct PendingLevelTransfer {
+    pub level: LevelIid,
+    pub player_moved: bool
+}
+
+impl PendingLevelTransfer {
+    pub fn new(level: LevelIid) -> Self {
+        PendingLevelTransfer { level, player_moved: false }
+    }
+}
+
 #[derive(Component)]
 pub struct GroundSensor {
     pub ground_detection_entity: Entity,
     pub intersecting_ground_entities: HashSet<Entity>,
 }
 
-#[derive(Default, Component)]
-pub struct Player;
+/// Detects walls on one side of the player, like `GroundSensor` does below it
+#[derive(Component)]
+pub struct WallSensor {
+    pub wall_detection_entity: Entity,
+    pub side: WallContact,
+    pub intersecting_wall_entities: HashSet<Entity>,
+}
 
 #[derive(Default, Component)]
-pub struct Grapple;
-
-#[derive(Default, Bundle)]
-pub struct GrapppleBundle {
-    grapple: Grapple,
-    sprite_bundle: SpriteBundle,
-    pub rigid_body: RigidBody,
-    pub velocity: Velocity,
-    pub rotation_constraints: LockedAxes,
-    pub gravity_scale: GravityScale,
-    pub friction: Friction,
-}
+pub struct Player;
 
 impl From<&EntityInstance> for ColliderBundle {
     fn from(_value: &EntityInstance) -> Self {
@@ -98,9 +227,7 @@ pub fn level_selection_follow_player(
     levels: Query<(&LevelIid, &GlobalTransform)>,
     ldtk_projects: Query<&Handle<LdtkProject>>,
     ldtk_project_assets: Res<Assets<LdtkProject>>,
-    mut level_selection: ResMut<LevelSelection>,
-    mut camera: Query<(&Transform, &mut CameraTransition, &OrthographicProjection)>,
-    time: Res<Time>
+    mut level_selection: ResMut<LevelSelection>
 ) {
     if let Ok(player_transform) = players.get_single() {
         let ldtk_project = ldtk_project_assets
@@ -127,79 +254,102 @@ pub fn level_selection_follow_player(
                 let new_level_selection = LevelSelection::Iid(level_iid.clone());
                 if new_level_selection != *level_selection {
                     *level_selection = new_level_selection;
-                    let (camera_transform, mut camera_transition, projection) = camera.single_mut();
-                    camera_transition.is_changing_level = true;
-                    camera_transition.begin_position = camera_transform.translation.xy();
-                    camera_transition.begin_time = time.elapsed_seconds();
-                    if let ScalingMode::Fixed { width, height } = projection.scaling_mode {
-                        camera_transition.begin_scale.x = width;
-                        camera_transition.begin_scale.y = height;
-                    }
                 }
             }
         }
     }
 }
 
-pub fn collide_with_spikes(
-    mut event: EventReader<CollisionEvent>,
-    level_selection: Res<LevelSelection>,
-    levels: Query<(&LevelIid, &Children)>,
-    spikes: Query<&Spikes>,
-    mut player: Query<&mut Transform, With<Player>>,
-    ldtk_projects: Query<&Handle<LdtkProject>>,
-    ldtk_project_assets: Res<Assets<LdtkProject>>,
-    spawnpoint: Query<&GlobalTransform, With<Spawnpoint>>,
-    entity_layer: Query<&Children, With<LayerMetadata>>
+pub fn movement(
+    actions: Res<PlayerActions>,
+    mut query: Query<(&mut Transform, &mut Velocity, &mut JumpComponent, &mut ClimbComponent, &mut GravityScale, &Collider), (With<Player>, Without<Dead>)>,
+    ladders: Query<(&GlobalTransform, &Collider), (With<Ladder>, Without<Player>)>,
+    grapple: Query<(), With<Grapple>>,
+    tuning: Res<MovementTuning>,
+    time: Res<Time>
 ) {
-    for event in event.read() {
-        if let CollisionEvent::Started(entity, entity_2, _flags) = event {
-            if (spikes.contains(*entity) && player.contains(*entity_2)) || (spikes.contains(*entity_2) && player.contains(*entity)) {
-                if let Some(only_project) = ldtk_project_assets.get(ldtk_projects.single()) {
-                    let level_selection_iid = LevelIid::new(
-                        only_project
-                            .find_raw_level_by_level_selection(&level_selection)
-                            .expect("spawned level should exist in project")
-                            .iid
-                            .clone(),
-                    );
-
-                    for (level_iid, children) in levels.iter() {
-                        if level_selection_iid == *level_iid {
-                            for &child in children.iter() {
-                                if let Ok(children) = entity_layer.get(child) {
-                                    for &child in children.iter() {
-                                        if let Ok(transform) = spawnpoint.get(child) {
-                                        let mut player_transform = player.single_mut();
-                                            player_transform.translation.x = transform.translation().x;
-                                            player_transform.translation.y = transform.translation().y;
-                                            panic!();
-                                            return;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
+    for (mut transform, mut velocity, mut jump_component, mut climb_component, mut gravity_scale, collider) in &mut query {
+        let right = actions.value(Action::MoveRight);
+        let left = actions.value(Action::MoveLeft);
+
+        let ground_material = jump_component.ground_material.filter(|_| jump_component.on_ground).unwrap_or_default();
+
+        let target_speed : f32 = (right - left) * tuning.run_speed * ground_material.speed_factor();
+        let speed_difference : f32 = target_speed - velocity.linvel.x;
+        let acceleration_per_reference_tick = if target_speed.abs() > 0.01 { tuning.acceleration } else { tuning.deceleration };
+        // The same fraction of the difference is left after a second at any tick rate
+        let acceleration_rate = 1.0 - (1.0 - acceleration_per_reference_tick * ground_material.grip()).powf(time.delta_seconds() * ACCELERATION_REFERENCE_RATE);
+        let input_locked = jump_component.input_locked_until.is_some_and(|until| time.elapsed_seconds() < until);
+        //let force = (speed_difference.abs() * acceleration_rate).powi(2) * speed_difference.signum();
+        // A and D pump the swing instead while hanging on the grapple, and are ignored for a moment after a wall jump
+        if grapple.is_empty() && !input_locked {
+            velocity.linvel.x += speed_difference * acceleration_rate;
+        }
+
+        let up = actions.value(Action::MoveUp);
+        let down = actions.value(Action::MoveDown);
+        let was_on_ladder = climb_component.climbing || climb_component.on_ladder_top;
+
+        if let Some(ladder_top) = top_of_intersecting_ladders(&climb_component, &ladders) {
+            let feet = transform.translation.y - collider.as_cuboid().map_or(0.0, |cuboid| cuboid.half_extents().y);
+            let at_top = feet >= ladder_top - LADDER_TOP_TOLERANCE;
+
+            if climb_component.climbing {
+                if at_top && up > 0.0 {
+                    // climbed out of the ladder
+                    climb_component.climbing = false;
+                    climb_component.on_ladder_top = true;
+                } else if jump_component.on_ground && down > 0.0 {
+                    // reached the bottom
+                    climb_component.climbing = false;
                 }
+            } else if climb_component.on_ladder_top {
+                if down > 0.0 {
+                    // drop through the top of the ladder
+                    climb_component.on_ladder_top = false;
+                    climb_component.climbing = true;
+                }
+            } else if !jump_component.jumping && grapple.is_empty() && ((up > 0.0 && !at_top) || (down > 0.0 && !jump_component.on_ground)) {
+                // Up and down reel the rope while grappled, so they only grab ladders without it
+                climb_component.climbing = true;
+            } else if at_top && feet <= ladder_top + LADDER_TOP_TOLERANCE && velocity.linvel.y <= 0.0 && !jump_component.jumping {
+                // landed on the top of the ladder
+                climb_component.on_ladder_top = true;
+            }
+
+            if climb_component.on_ladder_top && (feet < ladder_top - LADDER_TOP_TOLERANCE || feet > ladder_top) {
+                // keep the feet slightly inside the ladder so it keeps being detected
+                transform.translation.y += ladder_top - LADDER_TOP_TOLERANCE / 2.0 - feet;
             }
+        } else {
+            climb_component.climbing = false;
+            climb_component.on_ladder_top = false;
+        }
+
+        if climb_component.climbing {
+            velocity.linvel.y = (up - down) * tuning.climb_speed;
+            *gravity_scale = GravityScale(0.0);
+        } else if climb_component.on_ladder_top {
+            velocity.linvel.y = 0.0;
+            *gravity_scale = GravityScale(0.0);
+        } else if was_on_ladder {
+            *gravity_scale = GravityScale(1.0);
         }
-    }
-}
 
-pub fn movement(input: Res<ButtonInput<KeyCode>>, mut query: Query<(&mut Velocity, &mut JumpComponent, &mut GravityScale), With<Player>>, time: Res<Time>) {
-    for (mut velocity, mut jump_component, mut gravity_scale) in &mut query {
-        let right = if input.pressed(KeyCode::KeyD) { 1.0 } else { 0.0 };
-        let left = if input.pressed(KeyCode::KeyA) { 1.0 } else { 0.0 };
+        let against_wall = jump_component.wall_contact != WallContact::None && !jump_component.on_ground &&
+            !climb_component.climbing && !climb_component.on_ladder_top && grapple.is_empty();
+        if against_wall {
+            jump_component.last_on_wall = Some(time.elapsed_seconds());
+            jump_component.last_wall_contact = jump_component.wall_contact;
 
-        let target_speed : f32 = (right - left) * 90.0;
-        let speed_difference : f32 = target_speed - velocity.linvel.x;
-        let acceleration_rate = if target_speed.abs() > 0.01 { 0.1 } else { 0.15 };
-        //let force = (speed_difference.abs() * acceleration_rate).powi(2) * speed_difference.signum();
-        velocity.linvel.x += speed_difference * acceleration_rate;
+            let pressing_into_wall = (right - left) * jump_component.wall_contact.direction() > 0.0;
+            if pressing_into_wall && velocity.linvel.y < -tuning.wall_slide_speed {
+                velocity.linvel.y = -tuning.wall_slide_speed;
+            }
+        }
 
         if let Some(last_time_jumped) = jump_component.last_time_jumped {
-            if jump_component.jumping && (input.just_released(KeyCode::Space) || time.elapsed_seconds() - last_time_jumped > 0.5) {
+            if jump_component.jumping && (actions.just_released(Action::Jump) || time.elapsed_seconds() - last_time_jumped > tuning.jump_hold_time) {
                 /*
                 if velocity.linvel.y > 0.0 {
                     velocity.linvel.y /= 2.0;
@@ -211,127 +361,141 @@ pub fn movement(input: Res<ButtonInput<KeyCode>>, mut query: Query<(&mut Velocit
             }
         }
 
-        if input.just_pressed(KeyCode::Space) {
-            jump_component.last_tried_to_jump = Some(time.elapsed_seconds());
+        if actions.just_pressed(Action::Jump) {
+            if jump_component.on_one_way_platform && actions.pressed(Action::MoveDown) {
+                jump_component.dropping_through_until = Some(time.elapsed_seconds() + DROP_THROUGH_DURATION);
+                jump_component.on_ground = false;
+                jump_component.on_one_way_platform = false;
+                jump_component.last_on_ground = None;
+            } else {
+                jump_component.last_tried_to_jump = Some(time.elapsed_seconds());
+            }
         }
 
-        if jump_component.on_ground {
+        let on_ladder = climb_component.climbing || climb_component.on_ladder_top;
+
+        // Ladders count as ground for the grace period, so the player can jump off them
+        if jump_component.on_ground || on_ladder {
             jump_component.last_on_ground = Some(time.elapsed_seconds());
             jump_component.jumping = false;
             jump_component.falling = false;
-            *gravity_scale = GravityScale(1.0);
+            if !on_ladder {
+                *gravity_scale = GravityScale(1.0);
+            }
         }
 
-        if let (Some(last_on_ground), Some(last_tried_to_jump)) = (jump_component.last_on_ground, jump_component.last_tried_to_jump) {
-            if time.elapsed_seconds() - last_on_ground <= JUMP_GRACE_PERIOD &&
-                time.elapsed_seconds() - last_tried_to_jump <= JUMP_GRACE_PERIOD {
-                velocity.linvel.y = 70.0;
+        let now = time.elapsed_seconds();
+        let within_grace_period = |moment: Option<f32>| moment.is_some_and(|moment| now - moment <= tuning.jump_grace_period);
+
+        if within_grace_period(jump_component.last_tried_to_jump) {
+            // Jumping off the ground wins over jumping off a wall next to it
+            let wall_jump = !within_grace_period(jump_component.last_on_ground) && within_grace_period(jump_component.last_on_wall);
+
+            if within_grace_period(jump_component.last_on_ground) || wall_jump {
+                if wall_jump {
+                    velocity.linvel.x = -jump_component.last_wall_contact.direction() * tuning.wall_jump_push;
+                    jump_component.input_locked_until = Some(now + tuning.wall_jump_lock_time);
+                }
+
+                velocity.linvel.y = tuning.jump_velocity;
                 jump_component.last_on_ground = None;
+                jump_component.last_on_wall = None;
                 jump_component.on_ground = false;
                 jump_component.last_tried_to_jump = None;
                 jump_component.jumping = true;
-                jump_component.last_time_jumped = Some(time.elapsed_seconds());
-                *gravity_scale = GravityScale(0.15);
+                jump_component.last_time_jumped = Some(now);
+                climb_component.climbing = false;
+                climb_component.on_ladder_top = false;
+                *gravity_scale = GravityScale(tuning.jump_gravity_scale);
             }
         }
     }
 }
 
-pub fn grapple(
-    mut commands: Commands,
-    mut player: Query<(Entity, &Transform, &mut Velocity), With<Player>>,
-    grapple: Query<Entity, With<Grapple>>,
-    input: Res<ButtonInput<MouseButton>>,
-    window: Query<&Window, With<PrimaryWindow>>,
-    camera: Query<(&Camera, &GlobalTransform)>,
-    rapier_context: Res<RapierContext>,
-    hook: Query<&GlobalTransform, With<Hook>>
+/// Starts, holds and ends dashes. Runs after `movement` and overrides its velocity and gravity while dashing.
+pub fn dash(
+    actions: Res<PlayerActions>,
+    mut query: Query<(&mut Velocity, &mut JumpComponent, &mut ClimbComponent, &mut GravityScale, &mut DashComponent), (With<Player>, Without<Dead>)>,
+    attached_grapples: Query<(), Added<Grapple>>,
+    tuning: Res<MovementTuning>,
+    time: Res<Time>
 ) {
-    if input.just_pressed(MouseButton::Left) {
-
-        let (player_entity, player_transform, _) = player.single();
-        let (camera, camera_transform) = camera.single();
-        let window = window.single();
-        if let Some(cursor_pos) = window.cursor_position() {
-            if let Some(world_pos_cursor) = camera.viewport_to_world_2d(camera_transform, cursor_pos) {
-                
-                let hit_point = hook.iter().min_by_key(|h| {
-                    //println!("{}", h.translation());
-                    h.translation().xy().distance_squared(world_pos_cursor) as i32
-                }).expect("There should be a hook!").translation().xy();
-                //println!("{world_pos}, {world_pos_cursor}");
-
-                //let ray_dir = (world_pos - player_transform.translation.xy()).normalize();
-                //if let Some((_, toi)) = rapier_context.cast_ray(player_transform.translation.xy(), ray_dir, 80.0, true, QueryFilter::only_fixed()) {
-                    //let hit_point = player_transform.translation.xy() + ray_dir * toi;
-
-                    let joint = RopeJointBuilder::new(hit_point.distance(player_transform.translation.xy()))
-                        .local_anchor1(Vec2::new(0.0, 0.0))
-                        .local_anchor2(Vec2::new(0.0, 0.0)).build();
-
-                    commands.spawn(GrapppleBundle {
-                        sprite_bundle: SpriteBundle {
-                            sprite: Sprite {
-                                color: Color::RED,
-                                custom_size: Some(Vec2::new(50.0, 1.0)),
-                                anchor: bevy::sprite::Anchor::CenterLeft,
-                                ..default()
-                            },
-                            transform: Transform::from_translation(Vec3::new(hit_point.x, hit_point.y, 10.0)),
-                            ..default()
-                        },
-                        rigid_body: RigidBody::Dynamic,
-                        gravity_scale: GravityScale(0.0),
-                        ..default()
-                    }).insert(ImpulseJoint::new(player_entity, joint));
-                //}
-            }
+    let now = time.elapsed_seconds();
+    let dash_speed = tuning.dash_distance / tuning.dash_duration;
+
+    for (mut velocity, mut jump_component, mut climb_component, mut gravity_scale, mut dash_component) in &mut query {
+        let direction = Vec2::new(
+            actions.value(Action::MoveRight) - actions.value(Action::MoveLeft),
+            actions.value(Action::MoveUp) - actions.value(Action::MoveDown)
+        );
+        if direction.x != 0.0 {
+            dash_component.facing = direction.x.signum();
         }
-    }
 
-    if input.just_released(MouseButton::Left) {
-        if let Ok(entity) = grapple.get_single() {
-            commands.entity(entity).despawn();
+        let grapple_attached = !attached_grapples.is_empty();
+        if jump_component.on_ground || grapple_attached {
+            dash_component.available = true;
+        }
 
-            let (_, _, mut player_velocity) = player.single_mut();
-            if player_velocity.linvel.y > 0.0 {
-                player_velocity.linvel.y *= 1.5;
+        if let Some(dashing_until) = dash_component.dashing_until {
+            // The rope takes over from a dash
+            if now < dashing_until && !grapple_attached {
+                velocity.linvel = dash_component.direction * dash_speed;
+                *gravity_scale = GravityScale(0.0);
+                continue;
             }
-        }
-    }
-}
 
-pub fn grapple_look_at_player(
-    player: Query<&Transform, With<Player>>,
-    mut grapple: Query<(&mut Transform, &mut Sprite), (With<Grapple>, Without<Player>)>
-) {
-    if let Ok(player) = player.get_single() {
-        if let Ok((mut grapple, mut sprite)) = grapple.get_single_mut() {
-            let diff = player.translation - grapple.translation;
-            let angle = diff.y.atan2(diff.x);
-            grapple.rotation = Quat::from_axis_angle(Vec3::Z, angle);
-            sprite.custom_size = Some(Vec2::new(diff.length(), 1.0));
+            dash_component.dashing_until = None;
+            dash_component.cooldown_until = Some(now + tuning.dash_cooldown);
+            if !grapple_attached {
+                velocity.linvel = dash_component.direction * tuning.run_speed.min(dash_speed);
+            }
+            *gravity_scale = GravityScale(if climb_component.climbing || climb_component.on_ladder_top {
+                0.0
+            } else if jump_component.jumping {
+                tuning.jump_gravity_scale
+            } else {
+                1.0
+            });
         }
-    }
-}
 
-pub fn grapple_pull_player(
-    mut player: Query<(&Transform, &mut Velocity), With<Player>>,
-    mut grapple: Query<(&Transform, &mut ImpulseJoint), With<Grapple>>
-) {
-    if let Ok((transform, mut velocity)) = player.get_single_mut() {
-        if let Ok((grapple_transform, mut joint)) = grapple.get_single_mut() {
-            let mut difference = (grapple_transform.translation - transform.translation).xy();
-            difference.y /= 4.0;
-            difference.x /= 2.0;
-            velocity.linvel += difference;
-            if let Some(rope_joint) = joint.data.as_rope_mut() {
-                rope_joint.set_max_distance(rope_joint.max_distance().min(transform.translation.xy().distance(grapple_transform.translation.xy())));
+        let cooled_down = !dash_component.cooldown_until.is_some_and(|until| now < until);
+        if actions.just_pressed(Action::Dash) && dash_component.available && cooled_down {
+            dash_component.direction = if direction == Vec2::ZERO {
+                Vec2::new(dash_component.facing, 0.0)
+            } else {
+                direction.normalize()
+            };
+            dash_component.dashing_until = Some(now + tuning.dash_duration);
+            dash_component.available = false;
+
+            // A dash cuts the low gravity part of a jump short
+            if jump_component.jumping {
+                jump_component.jumping = false;
+                jump_component.falling = true;
             }
+            climb_component.climbing = false;
+            climb_component.on_ladder_top = false;
+
+            velocity.linvel = dash_component.direction * dash_speed;
+            *gravity_scale = GravityScale(0.0);
         }
     }
 }
 
+/// Highest point of the ladder volumes the player overlaps
+fn top_of_intersecting_ladders(
+    climb_component: &ClimbComponent,
+    ladders: &Query<(&GlobalTransform, &Collider), (With<Ladder>, Without<Player>)>
+) -> Option<f32> {
+    climb_component.intersecting_ladders.iter()
+        .filter_map(|&entity| ladders.get(entity).ok())
+        .map(|(transform, collider)| {
+            transform.translation().y + collider.as_cuboid().map_or(0.0, |cuboid| cuboid.half_extents().y)
+        })
+        .reduce(f32::max)
+}
+
 pub fn spawn_ground_sensor(
     mut commands: Commands,
     detect_ground_for: Query<(Entity, &Collider), Added<JumpComponent>>,
@@ -364,20 +528,51 @@ pub fn spawn_ground_sensor(
     }
 }
 
+pub fn spawn_wall_sensors(
+    mut commands: Commands,
+    detect_walls_for: Query<(Entity, &Collider), Added<JumpComponent>>,
+) {
+    for (entity, shape) in &detect_walls_for {
+        if let Some(cuboid) = shape.as_cuboid() {
+            let Vec2 {
+                x: half_extents_x,
+                y: half_extents_y,
+            } = cuboid.half_extents();
+
+            // Half as tall as the player, so the ground and the ceiling aren't taken for walls
+            let detector_shape = Collider::cuboid(2., half_extents_y / 2.0);
+
+            commands.entity(entity).with_children(|builder| {
+                for side in [WallContact::Left, WallContact::Right] {
+                    builder
+                        .spawn_empty()
+                        .insert(ActiveEvents::COLLISION_EVENTS)
+                        .insert(detector_shape.clone())
+                        .insert(Sensor)
+                        .insert(Transform::from_translation(Vec3::new(side.direction() * half_extents_x, 0., 0.)))
+                        .insert(GlobalTransform::default())
+                        .insert(WallSensor {
+                            wall_detection_entity: entity,
+                            side,
+                            intersecting_wall_entities: HashSet::new(),
+                        });
+                }
+            });
+        }
+    }
+}
+
 pub fn ground_detection(
     mut ground_sensors: Query<&mut GroundSensor>,
     mut collisions: EventReader<CollisionEvent>,
-    collidables: Query<(), (With<Collider>, Without<Sensor>)>,
-    push_platform: Query<(), With<PushPlatform>>,
-    mut player_velocity: Query<&mut Velocity, With<Player>>
+    // Springs launch the player rather than hold them up
+    collidables: Query<(), (With<Collider>, Without<Sensor>, Without<Spring>)>
 ) {
     for collision_event in collisions.read() {
         match collision_event {
             CollisionEvent::Started(e1, e2, _) => {
                 if collidables.contains(*e1) {
-                    if push_platform.contains(*e1) {
-                        player_velocity.single_mut().linvel.y = 300.0;
-                    } else if let Ok(mut sensor) = ground_sensors.get_mut(*e2) {
+                    if let Ok(mut sensor) = ground_sensors.get_mut(*e2) {
                         sensor.intersecting_ground_entities.insert(*e1);
                     }
                 } else if collidables.contains(*e2) {
@@ -401,13 +596,148 @@ pub fn ground_detection(
     }
 }
 
+/// Updates when the sensor touches or leaves something. One-way platforms are checked every tick,
+/// as they only count as ground while falling onto or standing on their top, not while jumping up through them.
 pub fn update_on_ground(
-    mut ground_detectors: Query<&mut JumpComponent>,
-    ground_sensors: Query<&GroundSensor, Changed<GroundSensor>>,
+    mut ground_detectors: Query<(&mut JumpComponent, &Transform, &Collider, &Velocity)>,
+    ground_sensors: Query<Ref<GroundSensor>>,
+    one_way_platforms: Query<(&GlobalTransform, &Collider), With<OneWayPlatform>>,
+    materials: Query<(&TileMaterial, &GlobalTransform, &Collider)>,
+    time: Res<Time>
 ) {
     for sensor in &ground_sensors {
-        if let Ok(mut ground_detection) = ground_detectors.get_mut(sensor.ground_detection_entity) {
-            ground_detection.on_ground = !sensor.intersecting_ground_entities.is_empty();
+        let touches_one_way_platform = sensor.intersecting_ground_entities.iter().any(|entity| one_way_platforms.contains(*entity));
+        // Which of several grounds the feet are mostly on changes while walking across them
+        let touches_several_grounds = sensor.intersecting_ground_entities.len() > 1;
+        if !sensor.is_changed() && !touches_one_way_platform && !touches_several_grounds {
+            continue;
+        }
+
+        let Ok((mut ground_detection, transform, collider, velocity)) = ground_detectors.get_mut(sensor.ground_detection_entity) else {
+            continue;
+        };
+
+        let feet = transform.translation.y - collider.as_cuboid().map_or(0.0, |cuboid| cuboid.half_extents().y);
+        let mut on_solid_ground = false;
+        let mut on_one_way_platform = false;
+
+        for entity in &sensor.intersecting_ground_entities {
+            match one_way_platforms.get(*entity) {
+                Ok((platform_transform, platform_collider)) => {
+                    let top = platform_transform.translation().y + platform_collider.as_cuboid().map_or(0.0, |cuboid| cuboid.half_extents().y);
+                    if velocity.linvel.y <= 0.0 && feet >= top - ONE_WAY_TOP_TOLERANCE && !ground_detection.dropping_through(time.elapsed_seconds()) {
+                        on_one_way_platform = true;
+                    }
+                }
+                Err(_) => on_solid_ground = true
+            }
+        }
+
+        let on_ground = on_solid_ground || on_one_way_platform;
+        let on_one_way_platform = on_one_way_platform && !on_solid_ground;
+        let ground_material = ground_material_under(&sensor, transform, collider, &materials);
+        if ground_detection.on_ground != on_ground || ground_detection.on_one_way_platform != on_one_way_platform || ground_detection.ground_material != ground_material {
+            ground_detection.on_ground = on_ground;
+            ground_detection.on_one_way_platform = on_one_way_platform;
+            ground_detection.ground_material = ground_material;
+        }
+    }
+}
+
+/// Material of the ground with the largest overlap with the player's feet, ties going to the older entity
+fn ground_material_under(
+    sensor: &GroundSensor,
+    transform: &Transform,
+    collider: &Collider,
+    materials: &Query<(&TileMaterial, &GlobalTransform, &Collider)>
+) -> Option<TileMaterial> {
+    let half_width = collider.as_cuboid().map_or(0.0, |cuboid| cuboid.half_extents().x);
+    let (left, right) = (transform.translation.x - half_width, transform.translation.x + half_width);
+
+    sensor.intersecting_ground_entities.iter()
+        .filter_map(|entity| {
+            let (material, ground_transform, ground_collider) = materials.get(*entity).ok()?;
+            let ground_half_width = ground_collider.as_cuboid().map_or(0.0, |cuboid| cuboid.half_extents().x);
+            let ground_x = ground_transform.translation().x;
+            let overlap = right.min(ground_x + ground_half_width) - left.max(ground_x - ground_half_width);
+            Some((overlap, *entity, *material))
+        })
+        .max_by(|(overlap, entity, _), (other_overlap, other_entity, _)| overlap.total_cmp(other_overlap).then(other_entity.cmp(entity)))
+        .map(|(_, _, material)| material)
+}
+
+pub fn wall_detection(
+    mut wall_sensors: Query<&mut WallSensor>,
+    mut collisions: EventReader<CollisionEvent>,
+    // The sides of one-way platforms can be passed through, so they aren't walls
+    collidables: Query<(), (With<Collider>, Without<Sensor>, Without<OneWayPlatform>)>
+) {
+    for collision_event in collisions.read() {
+        match collision_event {
+            CollisionEvent::Started(e1, e2, _) => {
+                for (wall, sensor) in [(e1, e2), (e2, e1)] {
+                    if collidables.contains(*wall) {
+                        if let Ok(mut sensor) = wall_sensors.get_mut(*sensor) {
+                            sensor.intersecting_wall_entities.insert(*wall);
+                        }
+                    }
+                }
+            }
+            CollisionEvent::Stopped(e1, e2, _) => {
+                for (wall, sensor) in [(e1, e2), (e2, e1)] {
+                    if let Ok(mut sensor) = wall_sensors.get_mut(*sensor) {
+                        sensor.intersecting_wall_entities.remove(wall);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A wall on both sides, e.g. in a narrow shaft, doesn't count as either
+pub fn update_wall_contact(
+    mut wall_detectors: Query<(Entity, &mut JumpComponent)>,
+    wall_sensors: Query<&WallSensor>
+) {
+    for (entity, mut jump_component) in &mut wall_detectors {
+        let touching = |side| wall_sensors.iter().any(|sensor| {
+            sensor.wall_detection_entity == entity && sensor.side == side && !sensor.intersecting_wall_entities.is_empty()
+        });
+
+        let wall_contact = match (touching(WallContact::Left), touching(WallContact::Right)) {
+            (true, false) => WallContact::Left,
+            (false, true) => WallContact::Right,
+            _ => WallContact::None
+        };
+        if jump_component.wall_contact != wall_contact {
+            jump_component.wall_contact = wall_contact;
+        }
+    }
+}
+
+pub fn ladder_detection(
+    mut climbers: Query<&mut ClimbComponent>,
+    mut collisions: EventReader<CollisionEvent>,
+    ladders: Query<(), With<Ladder>>
+) {
+    for collision_event in collisions.read() {
+        match collision_event {
+            CollisionEvent::Started(e1, e2, _) => {
+                for (ladder, climber) in [(e1, e2), (e2, e1)] {
+                    if ladders.contains(*ladder) {
+                        if let Ok(mut climb_component) = climbers.get_mut(*climber) {
+                            climb_component.intersecting_ladders.insert(*ladder);
+                        }
+                    }
+                }
+            }
+            CollisionEvent::Stopped(e1, e2, _) => {
+                for (ladder, climber) in [(e1, e2), (e2, e1)] {
+                    if let Ok(mut climb_component) = climbers.get_mut(*climber) {
+                        climb_component.intersecting_ladders.remove(ladder);
+                    }
+                }
+            }
         }
     }
 }
@@ -0,0 +1,591 @@
+use std::path::{Path, PathBuf};
+
+use bevy::{app::AppExit, prelude::*};
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+use thiserror::Error;
+
+use crate::{apply_tick_input, Action, ActionState, DashComponent, GameState, Health, JumpComponent, LatchedInput, MovementTuning, PendingLevelTransfer, Player, PlayerActions, Teleported, TickInput, TickRate, WallContact};
+
+/// First bytes of every replay file
+const REPLAY_MAGIC : &[u8; 4] = b"BGRP";
+/// Version of the replay format, see `Replay::to_bytes`
+pub const REPLAY_VERSION : u8 = 3;
+/// Longest replay that is loaded, four hours at 60 ticks a second
+const MAX_REPLAY_TICKS : u64 = 4 * 60 * 60 * 60;
+/// Fewest bytes a run of inputs takes: its length, the buttons and the aim flag
+const MIN_RUN_BYTES : usize = 4;
+
+/// Records the player's input every fixed tick and plays recorded runs back.
+///
+/// A replay starts on the first tick the player stands on the ground without a level transfer in progress,
+/// and stores the level and the player's state at that point along with the movement tuning and the tick rate.
+/// Playing it back moves the player there and feeds the recorded input instead of the devices.
+#[derive(Default)]
+pub struct ReplayPlugin {
+    /// Where replays are saved with `Action::SaveReplay`. `None` doesn't record unless a `ReplayRecorder` is inserted.
+    pub replay_dir: Option<PathBuf>
+}
+
+impl ReplayPlugin {
+    pub fn recording() -> Self {
+        ReplayPlugin {
+            replay_dir: dirs::data_dir().map(|dir| dir.join("bevygame").join("replays"))
+        }
+    }
+}
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        if let Some(replay_dir) = &self.replay_dir {
+            app.insert_resource(ReplayRecorder::new(replay_dir.clone()));
+        }
+
+        app.add_systems(FixedPreUpdate, (
+                feed_replay_input
+                    .before(apply_tick_input)
+                    .run_if(resource_exists::<ReplayPlayback>),
+                record_tick_input
+                    .after(apply_tick_input)
+                    .run_if(resource_exists::<ReplayRecorder>.and_then(not(resource_exists::<ReplayPlayback>)))
+            ).run_if(in_state(GameState::Playing)))
+            .add_systems(Update, save_replay_input.run_if(resource_exists::<ReplayRecorder>))
+            .add_systems(OnEnter(GameState::MainMenu), restart_recording);
+    }
+}
+
+/// A recorded run
+#[derive(Clone, PartialEq, Debug)]
+pub struct Replay {
+    /// Iid of the level the replay starts in
+    pub level: String,
+    /// Ticks per second the inputs were recorded at
+    pub tick_rate: f64,
+    pub start_position: Vec2,
+    pub start_velocity: Vec2,
+    pub start_state: ReplayStartState,
+    pub tuning: MovementTuning,
+    /// Input of every tick since the start
+    pub inputs: Vec<TickInput>
+}
+
+#[derive(Debug, Error)]
+pub enum ReplayError {
+    #[error("Could not access the replay file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Not a replay file")]
+    BadMagic,
+    #[error("Unsupported replay version {0}")]
+    UnsupportedVersion(u8),
+    #[error("The replay file ends unexpectedly")]
+    Truncated,
+    #[error("The replay file is corrupted: {0}")]
+    Corrupted(&'static str)
+}
+
+/// The rest of the player's state at the start of a replay.
+/// Times are relative to the first tick, negative ones are before it.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ReplayStartState {
+    pub gravity_scale: f32,
+    pub jumping: bool,
+    pub falling: bool,
+    pub last_on_ground: Option<f32>,
+    pub last_tried_to_jump: Option<f32>,
+    pub last_time_jumped: Option<f32>,
+    pub last_on_wall: Option<f32>,
+    pub last_wall_contact: WallContact,
+    pub input_locked_until: Option<f32>,
+    pub dropping_through_until: Option<f32>,
+    pub dash_available: bool,
+    pub dash_cooldown_until: Option<f32>,
+    pub facing: f32,
+    pub health: u32,
+    pub max_health: u32,
+    pub invulnerable_until: Option<f32>
+}
+
+impl ReplayStartState {
+    pub fn capture(jump: &JumpComponent, gravity_scale: &GravityScale, dash: &DashComponent, health: &Health, now: f32) -> Self {
+        let relative = |time: Option<f32>| time.map(|time| time - now);
+        ReplayStartState {
+            gravity_scale: gravity_scale.0,
+            jumping: jump.jumping,
+            falling: jump.falling,
+            last_on_ground: relative(jump.last_on_ground),
+            last_tried_to_jump: relative(jump.last_tried_to_jump),
+            last_time_jumped: relative(jump.last_time_jumped),
+            last_on_wall: relative(jump.last_on_wall),
+            last_wall_contact: jump.last_wall_contact,
+            input_locked_until: relative(jump.input_locked_until),
+            dropping_through_until: relative(jump.dropping_through_until),
+            dash_available: dash.available,
+            dash_cooldown_until: relative(dash.cooldown_until),
+            facing: dash.facing,
+            health: health.current,
+            max_health: health.max,
+            invulnerable_until: relative(health.invulnerable_until)
+        }
+    }
+
+    pub fn restore(&self, jump: &mut JumpComponent, gravity_scale: &mut GravityScale, dash: &mut DashComponent, health: &mut Health, now: f32) {
+        let absolute = |time: Option<f32>| time.map(|time| time + now);
+        gravity_scale.0 = self.gravity_scale;
+        jump.jumping = self.jumping;
+        jump.falling = self.falling;
+        jump.last_on_ground = absolute(self.last_on_ground);
+        jump.last_tried_to_jump = absolute(self.last_tried_to_jump);
+        jump.last_time_jumped = absolute(self.last_time_jumped);
+        jump.last_on_wall = absolute(self.last_on_wall);
+        jump.last_wall_contact = self.last_wall_contact;
+        jump.input_locked_until = absolute(self.input_locked_until);
+        jump.dropping_through_until = absolute(self.dropping_through_until);
+        dash.available = self.dash_available;
+        dash.cooldown_until = absolute(self.dash_cooldown_until);
+        dash.facing = self.facing;
+        health.current = self.health;
+        health.max = self.max_health;
+        health.invulnerable_until = absolute(self.invulnerable_until);
+    }
+
+    fn write(&self, bytes: &mut Vec<u8>) {
+        bytes.push(u8::from(self.jumping) | u8::from(self.falling) << 1 | u8::from(self.dash_available) << 2);
+        bytes.push(match self.last_wall_contact {
+            WallContact::None => 0,
+            WallContact::Left => 1,
+            WallContact::Right => 2
+        });
+        for value in [self.gravity_scale, self.facing] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        for time in [
+            self.last_on_ground,
+            self.last_tried_to_jump,
+            self.last_time_jumped,
+            self.last_on_wall,
+            self.input_locked_until,
+            self.dropping_through_until,
+            self.dash_cooldown_until,
+            self.invulnerable_until
+        ] {
+            write_optional_f32(bytes, time);
+        }
+        write_varint(bytes, u64::from(self.health));
+        write_varint(bytes, u64::from(self.max_health));
+    }
+
+    fn read(reader: &mut ByteReader) -> Result<Self, ReplayError> {
+        let flags = reader.u8()?;
+        let last_wall_contact = match reader.u8()? {
+            0 => WallContact::None,
+            1 => WallContact::Left,
+            2 => WallContact::Right,
+            _ => return Err(ReplayError::Corrupted("bad wall contact"))
+        };
+        let health = |value: u64| u32::try_from(value).map_err(|_| ReplayError::Corrupted("health is too large"));
+
+        Ok(ReplayStartState {
+            jumping: flags & 1 != 0,
+            falling: flags & 2 != 0,
+            dash_available: flags & 4 != 0,
+            last_wall_contact,
+            gravity_scale: reader.f32()?,
+            facing: reader.f32()?,
+            last_on_ground: reader.optional_f32()?,
+            last_tried_to_jump: reader.optional_f32()?,
+            last_time_jumped: reader.optional_f32()?,
+            last_on_wall: reader.optional_f32()?,
+            input_locked_until: reader.optional_f32()?,
+            dropping_through_until: reader.optional_f32()?,
+            dash_cooldown_until: reader.optional_f32()?,
+            invulnerable_until: reader.optional_f32()?,
+            health: health(reader.varint()?)?,
+            max_health: health(reader.varint()?)?
+        })
+    }
+}
+
+impl Replay {
+    /// Encodes the replay as
+    /// magic, version, level iid, tick rate, start position, velocity and state, tuning values, and the inputs.
+    /// The inputs are run length encoded, as they tend to stay the same for many ticks in a row.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(REPLAY_MAGIC);
+        bytes.push(REPLAY_VERSION);
+
+        write_varint(&mut bytes, self.level.len() as u64);
+        bytes.extend_from_slice(self.level.as_bytes());
+        bytes.extend_from_slice(&self.tick_rate.to_le_bytes());
+        for value in [self.start_position.x, self.start_position.y, self.start_velocity.x, self.start_velocity.y] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        self.start_state.write(&mut bytes);
+
+        let tuning = tuning_values(&self.tuning);
+        write_varint(&mut bytes, tuning.len() as u64);
+        for value in tuning {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let mut runs: Vec<(u64, TickInput)> = Vec::new();
+        for input in &self.inputs {
+            match runs.last_mut() {
+                Some((count, last)) if last == input => *count += 1,
+                _ => runs.push((1, *input))
+            }
+        }
+
+        write_varint(&mut bytes, runs.len() as u64);
+        for (count, input) in runs {
+            write_varint(&mut bytes, count);
+            bytes.extend_from_slice(&input.buttons.to_le_bytes());
+            match input.aim {
+                Some(aim) => {
+                    bytes.push(1);
+                    bytes.extend_from_slice(&aim.x.to_le_bytes());
+                    bytes.extend_from_slice(&aim.y.to_le_bytes());
+                }
+                None => bytes.push(0)
+            }
+        }
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Replay, ReplayError> {
+        let mut reader = ByteReader { bytes };
+
+        if reader.take(4)? != REPLAY_MAGIC {
+            return Err(ReplayError::BadMagic);
+        }
+        let version = reader.u8()?;
+        if version != REPLAY_VERSION {
+            return Err(ReplayError::UnsupportedVersion(version));
+        }
+
+        let level_length = reader.varint()? as usize;
+        let level = String::from_utf8(reader.take(level_length)?.to_vec())
+            .map_err(|_| ReplayError::Corrupted("level iid isn't UTF-8"))?;
+        let tick_rate = f64::from_le_bytes(reader.take(8)?.try_into().expect("8 bytes were taken"));
+        let start_position = Vec2::new(reader.f32()?, reader.f32()?);
+        let start_velocity = Vec2::new(reader.f32()?, reader.f32()?);
+        let start_state = ReplayStartState::read(&mut reader)?;
+
+        let tuning_count = reader.varint()?;
+        if tuning_count > (reader.bytes.len() / 4) as u64 {
+            return Err(ReplayError::Truncated);
+        }
+        let tuning_values: Vec<f32> = (0..tuning_count).map(|_| reader.f32()).collect::<Result<_, _>>()?;
+        let tuning = tuning_from_values(&tuning_values).ok_or(ReplayError::Corrupted("too many tuning values"))?;
+
+        let run_count = reader.varint()?;
+        if run_count > (reader.bytes.len() / MIN_RUN_BYTES) as u64 {
+            return Err(ReplayError::Truncated);
+        }
+        let mut inputs = Vec::new();
+        for _ in 0..run_count {
+            let count = reader.varint()?;
+            if count > MAX_REPLAY_TICKS - inputs.len() as u64 {
+                return Err(ReplayError::Corrupted("too many ticks"));
+            }
+            let buttons = u16::from_le_bytes([reader.u8()?, reader.u8()?]);
+            let aim = match reader.u8()? {
+                0 => None,
+                1 => Some(Vec2::new(reader.f32()?, reader.f32()?)),
+                _ => return Err(ReplayError::Corrupted("bad aim flag"))
+            };
+            inputs.extend(std::iter::repeat(TickInput { buttons, aim }).take(count as usize));
+        }
+
+        Ok(Replay { level, tick_rate, start_position, start_velocity, start_state, tuning, inputs })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ReplayError> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Replay, ReplayError> {
+        Replay::from_bytes(&std::fs::read(path)?)
+    }
+}
+
+/// The tuning values in a fixed order, so the replay doesn't depend on a text format.
+/// New fields go at the end, replays recorded before they existed get their defaults.
+fn tuning_fields(tuning: &mut MovementTuning) -> [&mut f32; 14] {
+    [
+        &mut tuning.run_speed,
+        &mut tuning.acceleration,
+        &mut tuning.deceleration,
+        &mut tuning.jump_velocity,
+        &mut tuning.jump_gravity_scale,
+        &mut tuning.jump_hold_time,
+        &mut tuning.jump_grace_period,
+        &mut tuning.climb_speed,
+        &mut tuning.wall_slide_speed,
+        &mut tuning.wall_jump_push,
+        &mut tuning.wall_jump_lock_time,
+        &mut tuning.dash_distance,
+        &mut tuning.dash_duration,
+        &mut tuning.dash_cooldown
+    ]
+}
+
+fn tuning_values(tuning: &MovementTuning) -> Vec<f32> {
+    tuning_fields(&mut tuning.clone()).map(|value| *value).to_vec()
+}
+
+fn tuning_from_values(values: &[f32]) -> Option<MovementTuning> {
+    let mut tuning = MovementTuning::default();
+    let fields = tuning_fields(&mut tuning);
+    if values.len() > fields.len() {
+        return None;
+    }
+
+    for (field, value) in fields.into_iter().zip(values) {
+        *field = *value;
+    }
+    Some(tuning)
+}
+
+fn write_optional_f32(bytes: &mut Vec<u8>, value: Option<f32>) {
+    match value {
+        Some(value) => {
+            bytes.push(1);
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        None => bytes.push(0)
+    }
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+struct ByteReader<'a> {
+    bytes: &'a [u8]
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], ReplayError> {
+        if self.bytes.len() < count {
+            return Err(ReplayError::Truncated);
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, ReplayError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn f32(&mut self) -> Result<f32, ReplayError> {
+        let bytes = self.take(4)?;
+        Ok(f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn optional_f32(&mut self) -> Result<Option<f32>, ReplayError> {
+        match self.u8()? {
+            0 => Ok(None),
+            1 => Ok(Some(self.f32()?)),
+            _ => Err(ReplayError::Corrupted("bad optional value flag"))
+        }
+    }
+
+    fn varint(&mut self) -> Result<u64, ReplayError> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(ReplayError::Corrupted("varint is too long"))
+    }
+}
+
+/// Records the current run while present
+#[derive(Resource)]
+pub struct ReplayRecorder {
+    pub replay_dir: PathBuf,
+    /// `None` until the replay can start
+    pub replay: Option<Replay>
+}
+
+impl ReplayRecorder {
+    pub fn new(replay_dir: PathBuf) -> Self {
+        ReplayRecorder { replay_dir, replay: None }
+    }
+}
+
+/// Plays a replay back in place of the player's input
+#[derive(Resource)]
+pub struct ReplayPlayback {
+    pub replay: Replay,
+    /// Index of the next input to play, `None` before the replay has started
+    pub tick: Option<usize>,
+    /// Close the app once the replay is over, e.g. for checking replays without a window
+    pub exit_when_finished: bool
+}
+
+impl ReplayPlayback {
+    pub fn new(replay: Replay, exit_when_finished: bool) -> Self {
+        ReplayPlayback { replay, tick: None, exit_when_finished }
+    }
+}
+
+/// Iid of the level the selection points to, if the project is loaded
+fn current_level_iid(
+    level_selection: &LevelSelection,
+    ldtk_projects: &Query<&Handle<LdtkProject>>,
+    ldtk_project_assets: &Assets<LdtkProject>
+) -> Option<String> {
+    ldtk_projects.get_single().ok()
+        .and_then(|handle| ldtk_project_assets.get(handle))
+        .and_then(|project| project.find_raw_level_by_level_selection(level_selection))
+        .map(|level| level.iid.clone())
+}
+
+pub fn record_tick_input(
+    mut recorder: ResMut<ReplayRecorder>,
+    player_actions: Res<PlayerActions>,
+    player: Query<(&Transform, &Velocity, &JumpComponent, &GravityScale, &DashComponent, &Health), With<Player>>,
+    transfer: Option<Res<PendingLevelTransfer>>,
+    level_selection: Res<LevelSelection>,
+    ldtk_projects: Query<&Handle<LdtkProject>>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+    tuning: Res<MovementTuning>,
+    tick_rate: Res<TickRate>,
+    time: Res<Time>
+) {
+    if let Some(replay) = &mut recorder.replay {
+        replay.inputs.push(player_actions.input);
+        return;
+    }
+
+    let Ok((transform, velocity, jump_component, gravity_scale, dash_component, health)) = player.get_single() else {
+        return;
+    };
+    if transfer.is_some() || !jump_component.on_ground {
+        return;
+    }
+
+    if let Some(level) = current_level_iid(&level_selection, &ldtk_projects, &ldtk_project_assets) {
+        recorder.replay = Some(Replay {
+            level,
+            tick_rate: tick_rate.0,
+            start_position: transform.translation.xy(),
+            start_velocity: velocity.linvel,
+            start_state: ReplayStartState::capture(jump_component, gravity_scale, dash_component, health, time.elapsed_seconds()),
+            tuning: tuning.clone(),
+            inputs: vec![player_actions.input]
+        });
+    }
+}
+
+/// A new world starts a new run
+pub fn restart_recording(recorder: Option<ResMut<ReplayRecorder>>) {
+    if let Some(mut recorder) = recorder {
+        recorder.replay = None;
+    }
+}
+
+pub fn save_replay_input(actions: Res<ActionState>, recorder: Res<ReplayRecorder>) {
+    if !actions.just_pressed(Action::SaveReplay) {
+        return;
+    }
+
+    let Some(replay) = &recorder.replay else {
+        warn!("Nothing has been recorded yet");
+        return;
+    };
+
+    let seconds = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let path = recorder.replay_dir.join(format!("replay-{seconds}.replay"));
+
+    match replay.save(&path) {
+        Ok(()) => info!("Saved the replay to {}", path.display()),
+        Err(error) => error!("Could not save the replay to {}: {error}", path.display())
+    }
+}
+
+/// Moves the player to the start of the replay and then feeds its input tick by tick
+pub fn feed_replay_input(
+    mut commands: Commands,
+    mut playback: ResMut<ReplayPlayback>,
+    mut latched: ResMut<LatchedInput>,
+    mut player: Query<(Entity, &mut Transform, &mut Velocity, &mut JumpComponent, &mut GravityScale, &mut DashComponent, &mut Health), With<Player>>,
+    transfer: Option<Res<PendingLevelTransfer>>,
+    level_selection: Res<LevelSelection>,
+    ldtk_projects: Query<&Handle<LdtkProject>>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+    mut tuning: ResMut<MovementTuning>,
+    mut tick_rate: ResMut<TickRate>,
+    mut app_exit: EventWriter<AppExit>,
+    time: Res<Time>
+) {
+    let Some(tick) = playback.tick else {
+        // Nothing is pressed while waiting for the start
+        latched.replayed = Some(TickInput::default());
+
+        let Ok((player_entity, mut transform, mut velocity, mut jump_component, mut gravity_scale, mut dash_component, mut health)) = player.get_single_mut() else {
+            return;
+        };
+        if transfer.is_some() {
+            return;
+        }
+        // The new rate takes effect from the next frame
+        if tick_rate.0 != playback.replay.tick_rate {
+            tick_rate.0 = playback.replay.tick_rate;
+            return;
+        }
+
+        match current_level_iid(&level_selection, &ldtk_projects, &ldtk_project_assets) {
+            Some(level) if level == playback.replay.level => {
+                if jump_component.on_ground {
+                    transform.translation.x = playback.replay.start_position.x;
+                    transform.translation.y = playback.replay.start_position.y;
+                    velocity.linvel = playback.replay.start_velocity;
+                    commands.entity(player_entity).insert(Teleported);
+                    playback.replay.start_state.restore(&mut jump_component, &mut gravity_scale, &mut dash_component, &mut health, time.elapsed_seconds());
+                    *tuning = playback.replay.tuning.clone();
+                    latched.replayed = playback.replay.inputs.first().copied();
+                    playback.tick = Some(1);
+                }
+            }
+            Some(_) => commands.insert_resource(PendingLevelTransfer::new(LevelIid::new(playback.replay.level.clone()))),
+            None => {}
+        }
+        return;
+    };
+
+    match playback.replay.inputs.get(tick) {
+        Some(input) => {
+            latched.replayed = Some(*input);
+            playback.tick = Some(tick + 1);
+        }
+        None => {
+            info!("The replay has finished after {tick} ticks");
+            if playback.exit_when_finished {
+                app_exit.send(AppExit);
+            }
+            commands.remove_resource::<ReplayPlayback>();
+        }
+    }
+}
@@ -0,0 +1,282 @@
+use std::{collections::{BTreeMap, BTreeSet}, path::{Path, PathBuf}};
+
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{spawn_ldtk_world, ActiveCheckpoint, CheckpointLocation, CheckpointPlugin, GameState, LevelCompleteAction, LevelCompleted, PendingLevelTransfer, PlayerDied};
+
+/// Version written to new save files, see `migrate_save`
+pub const SAVE_VERSION : u32 = 2;
+
+/// Keeps the player's progress in a save file and resumes from it
+pub struct SavePlugin {
+    /// Where the save is read from and written to. `None` keeps it in memory only.
+    pub save_path: Option<PathBuf>
+}
+
+impl Default for SavePlugin {
+    fn default() -> Self {
+        SavePlugin {
+            save_path: dirs::data_dir().map(|dir| dir.join("bevygame").join("save.ron"))
+        }
+    }
+}
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        let save = match &self.save_path {
+            Some(path) => load_save(path),
+            None => SaveData::default()
+        };
+
+        if !app.is_plugin_added::<CheckpointPlugin>() {
+            app.add_plugins(CheckpointPlugin);
+        }
+
+        app.insert_resource(save.settings.level_complete_action)
+            .insert_resource(save)
+            .insert_resource(SaveFile(self.save_path.clone()))
+            .add_systems(OnEnter(GameState::Playing), resume_saved_level.before(spawn_ldtk_world))
+            .add_systems(Update, (record_level_completed, record_deaths, record_last_level, record_settings, record_active_checkpoint))
+            .add_systems(Last, write_save);
+    }
+}
+
+/// Progress and settings that are kept between runs
+#[derive(Resource, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct SaveData {
+    pub version: u32,
+    /// Iids of the levels whose goal has been reached
+    pub completed_levels: BTreeSet<String>,
+    /// Fastest completion of each level in seconds
+    pub best_times: BTreeMap<String, f32>,
+    pub deaths: BTreeMap<String, u32>,
+    /// Iid of the level the player was in last
+    pub last_level: Option<String>,
+    /// Checkpoint the player respawns at, added in version 2
+    pub active_checkpoint: Option<CheckpointLocation>,
+    pub settings: Settings
+}
+
+impl Default for SaveData {
+    fn default() -> Self {
+        SaveData {
+            version: SAVE_VERSION,
+            completed_levels: BTreeSet::new(),
+            best_times: BTreeMap::new(),
+            deaths: BTreeMap::new(),
+            last_level: None,
+            active_checkpoint: None,
+            settings: Settings::default()
+        }
+    }
+}
+
+/// `SaveData` as written by version 1, before checkpoints
+#[derive(Deserialize)]
+struct SaveDataV1 {
+    completed_levels: BTreeSet<String>,
+    best_times: BTreeMap<String, f32>,
+    deaths: BTreeMap<String, u32>,
+    last_level: Option<String>,
+    settings: Settings
+}
+
+impl From<SaveDataV1> for SaveData {
+    fn from(save: SaveDataV1) -> Self {
+        SaveData {
+            version: SAVE_VERSION,
+            completed_levels: save.completed_levels,
+            best_times: save.best_times,
+            deaths: save.deaths,
+            last_level: save.last_level,
+            active_checkpoint: None,
+            settings: save.settings
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct Settings {
+    pub level_complete_action: LevelCompleteAction
+}
+
+impl SaveData {
+    /// The first level and every level right after a completed one can be played
+    pub fn is_unlocked(&self, levels: &[LevelIid], index: usize) -> bool {
+        index == 0 || levels.get(index - 1).is_some_and(|previous| self.completed_levels.contains(previous.get()))
+    }
+
+    /// Marks a level as completed and keeps the time if it is the best one so far
+    pub fn complete_level(&mut self, level: &LevelIid, time: f32) {
+        self.completed_levels.insert(level.get().clone());
+        let best_time = self.best_times.entry(level.get().clone()).or_insert(time);
+        *best_time = best_time.min(time);
+    }
+}
+
+/// Path the `SaveData` is written to
+#[derive(Resource)]
+pub struct SaveFile(pub Option<PathBuf>);
+
+#[derive(Debug, Error)]
+pub enum SaveError {
+    #[error("Could not access the save file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse the save file: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+    #[error("Could not serialize the save: {0}")]
+    Serialize(#[from] ron::Error),
+    #[error("The save file is from a newer version of the game ({0}, this one supports up to {SAVE_VERSION})")]
+    TooNew(u32),
+    #[error("The save file has an unknown version {0}")]
+    UnknownVersion(u32)
+}
+
+/// Only the version of a save file, read before the rest to know how to parse it
+#[derive(Deserialize)]
+struct SaveVersion {
+    version: u32
+}
+
+/// Parses a save file of any known version into the current `SaveData`.
+///
+/// When the format changes, bump `SAVE_VERSION`, keep the previous struct around
+/// and add a match arm that parses it and converts it into the next version.
+pub fn migrate_save(text: &str) -> Result<SaveData, SaveError> {
+    let SaveVersion { version } = ron::from_str(text)?;
+
+    match version {
+        SAVE_VERSION => Ok(ron::from_str(text)?),
+        1 => Ok(ron::from_str::<SaveDataV1>(text)?.into()),
+        version if version > SAVE_VERSION => Err(SaveError::TooNew(version)),
+        version => Err(SaveError::UnknownVersion(version))
+    }
+}
+
+/// Reads the save file. A missing file starts a new save,
+/// a broken one is logged and left alone so it can be recovered by hand.
+pub fn load_save(path: &Path) -> SaveData {
+    let result = std::fs::read_to_string(path)
+        .map_err(SaveError::from)
+        .and_then(|text| migrate_save(&text));
+
+    match result {
+        Ok(save) => save,
+        Err(SaveError::Io(error)) if error.kind() == std::io::ErrorKind::NotFound => SaveData::default(),
+        Err(error) => {
+            error!("Could not load the save from {}, starting a new one: {error}", path.display());
+            SaveData::default()
+        }
+    }
+}
+
+/// Writes the save next to the old one first and then replaces it,
+/// so a crash while writing can't leave a half written save behind
+pub fn write_save_atomically(path: &Path, save: &SaveData) -> Result<(), SaveError> {
+    let text = ron::ser::to_string_pretty(save, ron::ser::PrettyConfig::default())?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let temporary_path = path.with_extension("ron.tmp");
+    std::fs::write(&temporary_path, text)?;
+    std::fs::rename(&temporary_path, path)?;
+    Ok(())
+}
+
+pub fn write_save(save: Res<SaveData>, file: Res<SaveFile>) {
+    // Nothing has changed since the save was read
+    if !save.is_changed() || save.is_added() {
+        return;
+    }
+
+    if let Some(path) = &file.0 {
+        if let Err(error) = write_save_atomically(path, &save) {
+            error!("Could not write the save to {}: {error}", path.display());
+        }
+    }
+}
+
+/// Moves the player to the saved level when a new game world is spawned, and back to the saved checkpoint if it is in that level.
+/// A checkpoint in another level stays active for the next respawn.
+/// The player is spawned in the first level, so the world starts there and the player is transferred afterwards.
+pub fn resume_saved_level(
+    mut commands: Commands,
+    save: Res<SaveData>,
+    worlds: Query<(), With<Handle<LdtkProject>>>,
+    mut active_checkpoint: ResMut<ActiveCheckpoint>
+) {
+    // Resuming from the pause screen, the world is still there
+    if !worlds.is_empty() {
+        return;
+    }
+
+    active_checkpoint.0 = save.active_checkpoint.clone();
+
+    if let Some(last_level) = &save.last_level {
+        commands.insert_resource(PendingLevelTransfer::new(LevelIid::new(last_level.clone())));
+    }
+}
+
+pub fn record_level_completed(mut level_completed: EventReader<LevelCompleted>, mut save: ResMut<SaveData>) {
+    for LevelCompleted { level, time } in level_completed.read() {
+        save.complete_level(level, *time);
+    }
+}
+
+pub fn record_deaths(
+    mut player_died: EventReader<PlayerDied>,
+    level_selection: Res<LevelSelection>,
+    ldtk_projects: Query<&Handle<LdtkProject>>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+    mut save: ResMut<SaveData>
+) {
+    for _ in player_died.read() {
+        let level = ldtk_projects.get_single().ok()
+            .and_then(|handle| ldtk_project_assets.get(handle))
+            .and_then(|project| project.find_raw_level_by_level_selection(&level_selection));
+
+        if let Some(level) = level {
+            *save.deaths.entry(level.iid.clone()).or_default() += 1;
+        }
+    }
+}
+
+pub fn record_last_level(
+    level_selection: Res<LevelSelection>,
+    transfer: Option<Res<PendingLevelTransfer>>,
+    state: Res<State<GameState>>,
+    mut save: ResMut<SaveData>
+) {
+    // The selection is only reset to the first level when the world is torn down,
+    // and it passes through the first level while a resumed game moves the player to the saved one
+    if *state.get() != GameState::Playing || transfer.is_some() {
+        return;
+    }
+
+    if let LevelSelection::Iid(level) = &*level_selection {
+        if save.last_level.as_ref() != Some(level.get()) {
+            save.last_level = Some(level.get().clone());
+        }
+    }
+}
+
+pub fn record_settings(level_complete_action: Res<LevelCompleteAction>, mut save: ResMut<SaveData>) {
+    if level_complete_action.is_changed() && save.settings.level_complete_action != *level_complete_action {
+        save.settings.level_complete_action = *level_complete_action;
+    }
+}
+
+pub fn record_active_checkpoint(active_checkpoint: Res<ActiveCheckpoint>, state: Res<State<GameState>>, mut save: ResMut<SaveData>) {
+    // It is reset in the main menu and only restored from the save when playing again
+    if *state.get() != GameState::Playing || !active_checkpoint.is_changed() {
+        return;
+    }
+
+    if save.active_checkpoint != active_checkpoint.0 {
+        save.active_checkpoint = active_checkpoint.0.clone();
+    }
+}
@@ -0,0 +1,159 @@
+use bevy::{prelude::*, sprite::Anchor};
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{GameSet, GameSetsPlugin};
+
+/// Launch speed of springs without a `strength` field, what the push platform used to have
+const DEFAULT_SPRING_STRENGTH : f32 = 300.0;
+/// Size of the `Push_platform` tile
+const SPRING_SIZE : f32 = 16.0;
+/// How long the compress animation takes
+const SPRING_COMPRESS_DURATION : f32 = 0.25;
+/// Fraction of its size the spring is squashed to at the start of the animation
+const SPRING_COMPRESSED_SCALE : f32 = 0.5;
+
+/// Springs placed as `PushPlatform` entities in LDtk
+pub struct SpringPlugin;
+
+impl Plugin for SpringPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<GameSetsPlugin>() {
+            app.add_plugins(GameSetsPlugin);
+        }
+
+        app.register_ldtk_entity::<SpringBundle>("PushPlatform")
+            .add_systems(FixedUpdate, launch_from_springs.in_set(GameSet::GroundDetection))
+            .add_systems(Update, animate_springs);
+    }
+}
+
+/// Launches dynamic bodies that touch it.
+///
+/// Read from the optional fields of the LDtk entity:
+/// `angle` (Float, degrees clockwise from straight up, 0 by default),
+/// `strength` (Float, launch speed, 300 by default) and
+/// `additive` (Bool, adds to the velocity instead of replacing the part of it along the launch direction, false by default).
+#[derive(Clone, Component, Debug)]
+pub struct Spring {
+    /// Normalized launch direction
+    pub direction: Vec2,
+    pub strength: f32,
+    pub additive: bool,
+    /// When the compress animation started
+    pub compressed_at: Option<f32>
+}
+
+impl Default for Spring {
+    fn default() -> Self {
+        Spring {
+            direction: Vec2::Y,
+            strength: DEFAULT_SPRING_STRENGTH,
+            additive: false,
+            compressed_at: None
+        }
+    }
+}
+
+impl From<&EntityInstance> for Spring {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        let default = Spring::default();
+        let angle = entity_instance.get_float_field("angle").map_or(0.0, |angle| angle.to_radians());
+
+        Spring {
+            direction: Vec2::new(angle.sin(), angle.cos()),
+            strength: entity_instance.get_float_field("strength").copied().unwrap_or(default.strength),
+            additive: entity_instance.get_bool_field("additive").copied().unwrap_or(default.additive),
+            ..default
+        }
+    }
+}
+
+impl Spring {
+    /// Velocity of a body launched by the spring
+    pub fn launch(&self, velocity: Vec2) -> Vec2 {
+        if self.additive {
+            velocity + self.direction * self.strength
+        } else {
+            velocity - self.direction * velocity.dot(self.direction) + self.direction * self.strength
+        }
+    }
+}
+
+#[derive(Default, Bundle, LdtkEntity)]
+pub struct SpringBundle {
+    #[from_entity_instance]
+    spring: Spring,
+
+    #[sprite_bundle("push_platform.png")]
+    sprite_bundle: SpriteBundle,
+
+    #[with(spring_collider_init)]
+    collider: Collider,
+
+    /// Bodies that don't report their own collisions get launched too
+    #[with(spring_active_events)]
+    active_events: ActiveEvents
+}
+
+fn spring_collider_init(_value: &EntityInstance) -> Collider {
+    Collider::cuboid(SPRING_SIZE / 2.0, SPRING_SIZE / 2.0)
+}
+
+fn spring_active_events(_value: &EntityInstance) -> ActiveEvents {
+    ActiveEvents::COLLISION_EVENTS
+}
+
+/// Works the same whichever way around the collision pair is reported
+pub fn launch_from_springs(
+    mut collisions: EventReader<CollisionEvent>,
+    mut springs: Query<&mut Spring>,
+    mut bodies: Query<(&RigidBody, &mut Velocity), Without<Spring>>,
+    time: Res<Time>
+) {
+    for collision_event in collisions.read() {
+        let CollisionEvent::Started(e1, e2, _) = collision_event else {
+            continue;
+        };
+
+        for (spring_entity, body_entity) in [(e1, e2), (e2, e1)] {
+            let (Ok(mut spring), Ok((rigid_body, mut velocity))) = (springs.get_mut(*spring_entity), bodies.get_mut(*body_entity)) else {
+                continue;
+            };
+
+            if *rigid_body == RigidBody::Dynamic {
+                velocity.linvel = spring.launch(velocity.linvel);
+                spring.compressed_at = Some(time.elapsed_seconds());
+            }
+        }
+    }
+}
+
+/// Squashes the spring towards its base and lets it spring back out
+pub fn animate_springs(mut springs: Query<(&mut Spring, &mut Sprite)>, time: Res<Time>) {
+    for (mut spring, mut sprite) in &mut springs {
+        let Some(compressed_at) = spring.compressed_at else {
+            continue;
+        };
+
+        let progress = ((time.elapsed_seconds() - compressed_at) / SPRING_COMPRESS_DURATION).clamp(0.0, 1.0);
+        let scale = SPRING_COMPRESSED_SCALE + (1.0 - SPRING_COMPRESSED_SCALE) * progress;
+
+        // Squash along the launch direction, keeping the side opposite to it in place
+        let vertical = spring.direction.y.abs() >= spring.direction.x.abs();
+        let offset = 0.5 / scale - 0.5;
+        let (size, anchor) = if vertical {
+            (Vec2::new(SPRING_SIZE, SPRING_SIZE * scale), Vec2::new(0.0, spring.direction.y.signum() * offset))
+        } else {
+            (Vec2::new(SPRING_SIZE * scale, SPRING_SIZE), Vec2::new(spring.direction.x.signum() * offset, 0.0))
+        };
+        sprite.custom_size = Some(size);
+        sprite.anchor = Anchor::Custom(anchor);
+
+        if progress >= 1.0 {
+            sprite.custom_size = None;
+            sprite.anchor = Anchor::Center;
+            spring.compressed_at = None;
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use bevy::{asset::LoadState, prelude::*};
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::{Action, ActionInputPlugin, ActionState, GameSetsPlugin};
+
+/// Where the game is at as a whole. Gameplay only runs while `Playing`.
+#[derive(States, Default, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum GameState {
+    /// Loading the LDtk project before showing the menu
+    #[default]
+    Boot,
+    MainMenu,
+    Playing,
+    /// Gameplay and physics are frozen, the world stays spawned
+    Paused,
+    /// A results or level select screen is shown after reaching a goal
+    LevelComplete
+}
+
+/// Main menu and pause screens and the transitions between them
+pub struct GameStatePlugin;
+
+impl Plugin for GameStatePlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<GameSetsPlugin>() {
+            app.add_plugins(GameSetsPlugin);
+        }
+        if !app.is_plugin_added::<ActionInputPlugin>() {
+            app.add_plugins(ActionInputPlugin::default());
+        }
+
+        app.add_systems(Startup, start_loading_project)
+            .add_systems(Update, finish_boot.run_if(in_state(GameState::Boot)))
+            .add_systems(OnEnter(GameState::MainMenu), spawn_main_menu)
+            .add_systems(OnExit(GameState::MainMenu), despawn_screen::<MainMenuScreen>)
+            .add_systems(Update, main_menu_input.run_if(in_state(GameState::MainMenu)))
+            .add_systems(OnEnter(GameState::Paused), spawn_pause_screen)
+            .add_systems(OnExit(GameState::Paused), despawn_screen::<PauseScreen>)
+            .add_systems(Update, pause_input.run_if(in_state(GameState::Playing).or_else(in_state(GameState::Paused))));
+    }
+}
+
+/// Keeps the LDtk project loaded from the start, so entering the game doesn't wait for it
+#[derive(Resource)]
+pub struct ProjectLoading(pub Handle<LdtkProject>);
+
+#[derive(Component)]
+pub struct MainMenuScreen;
+
+#[derive(Component)]
+pub struct PauseScreen;
+
+pub fn start_loading_project(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(ProjectLoading(asset_server.load("gamemap.ldtk")));
+}
+
+pub fn finish_boot(
+    project: Res<ProjectLoading>,
+    asset_server: Res<AssetServer>,
+    mut next_state: ResMut<NextState<GameState>>
+) {
+    match asset_server.load_state(&project.0) {
+        LoadState::Loaded => next_state.set(GameState::MainMenu),
+        LoadState::Failed => {
+            error!("Could not load the LDtk project");
+            next_state.set(GameState::MainMenu);
+        }
+        _ => {}
+    }
+}
+
+/// A full screen of text, despawned again with `despawn_screen::<T>` for its marker
+pub fn spawn_screen(commands: &mut Commands, text: &str, marker: impl Component) {
+    commands.spawn((
+        TextBundle::from_section(text, TextStyle { font_size: 32.0, ..default() })
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(20.0),
+                left: Val::Px(20.0),
+                ..default()
+            }),
+        marker
+    ));
+}
+
+pub fn spawn_main_menu(mut commands: Commands) {
+    spawn_screen(&mut commands, "bevygame\nPress Enter or A to play", MainMenuScreen);
+}
+
+pub fn spawn_pause_screen(mut commands: Commands) {
+    spawn_screen(&mut commands, "Paused\nEsc or Start to resume\nQ or Select to quit to the menu", PauseScreen);
+}
+
+pub fn despawn_screen<T: Component>(mut commands: Commands, screens: Query<Entity, With<T>>) {
+    for entity in &screens {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn main_menu_input(actions: Res<ActionState>, mut next_state: ResMut<NextState<GameState>>) {
+    if actions.just_pressed(Action::Confirm) {
+        next_state.set(GameState::Playing);
+    }
+}
+
+pub fn pause_input(
+    actions: Res<ActionState>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>
+) {
+    match state.get() {
+        GameState::Playing if actions.just_pressed(Action::Pause) => next_state.set(GameState::Paused),
+        GameState::Paused if actions.just_pressed(Action::Pause) => next_state.set(GameState::Playing),
+        GameState::Paused if actions.just_pressed(Action::Quit) => next_state.set(GameState::MainMenu),
+        _ => {}
+    }
+}
@@ -0,0 +1,189 @@
+use bevy::{asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext}, prelude::*, utils::BoxedFuture};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{ReplayPlayback, CLIMB_SPEED, JUMP_GRACE_PERIOD};
+
+/// Tuning file the player controller reads its parameters from, relative to the assets folder
+pub const MOVEMENT_TUNING_PATH : &str = "movement.tuning.ron";
+
+/// Loads `MovementTuning` from the assets folder and keeps the resource in sync with the file.
+/// With Bevy's `file_watcher` feature the file is reloaded whenever it is saved.
+pub struct TuningPlugin;
+
+impl Plugin for TuningPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<MovementTuning>()
+            .init_asset_loader::<MovementTuningLoader>()
+            .init_resource::<MovementTuning>()
+            .add_systems(Startup, load_movement_tuning)
+            // A replay brings its own tuning
+            .add_systems(PreUpdate, apply_movement_tuning.run_if(not(resource_exists::<ReplayPlayback>)));
+    }
+}
+
+/// Parameters of the player controller
+#[derive(Asset, TypePath, Resource, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct MovementTuning {
+    /// Horizontal speed the player accelerates towards while A or D is held
+    pub run_speed: f32,
+    /// Fraction of the difference to the target speed applied every 1/60 of a second while moving
+    pub acceleration: f32,
+    /// Fraction of the difference to the target speed applied every 1/60 of a second while stopping
+    pub deceleration: f32,
+    /// Vertical velocity set at the start of a jump
+    pub jump_velocity: f32,
+    /// Gravity scale while the jump button is held
+    pub jump_gravity_scale: f32,
+    /// How long the jump button can be held for the low gravity
+    pub jump_hold_time: f32,
+    /// How early a jump can be pressed before landing and how late after leaving the ground
+    pub jump_grace_period: f32,
+    pub climb_speed: f32,
+    /// Fastest the player falls while pressing into a wall
+    pub wall_slide_speed: f32,
+    /// Horizontal velocity away from the wall at the start of a wall jump
+    pub wall_jump_push: f32,
+    /// How long horizontal input is ignored after a wall jump
+    pub wall_jump_lock_time: f32,
+    /// How far a dash carries the player
+    pub dash_distance: f32,
+    /// How long a dash takes, gravity is ignored in the meantime
+    pub dash_duration: f32,
+    /// Time after the end of a dash before the next one
+    pub dash_cooldown: f32
+}
+
+impl Default for MovementTuning {
+    fn default() -> Self {
+        MovementTuning {
+            run_speed: 90.0,
+            acceleration: 0.1,
+            deceleration: 0.15,
+            jump_velocity: 70.0,
+            jump_gravity_scale: 0.15,
+            jump_hold_time: 0.5,
+            jump_grace_period: JUMP_GRACE_PERIOD,
+            climb_speed: CLIMB_SPEED,
+            wall_slide_speed: 30.0,
+            wall_jump_push: 80.0,
+            wall_jump_lock_time: 0.15,
+            dash_distance: 48.0,
+            dash_duration: 0.15,
+            dash_cooldown: 0.3
+        }
+    }
+}
+
+impl MovementTuning {
+    /// Checks that the values make sense, returning a description of the first one that doesn't
+    pub fn validate(&self) -> Result<(), String> {
+        let positive = [
+            ("run_speed", self.run_speed),
+            ("jump_velocity", self.jump_velocity),
+            ("climb_speed", self.climb_speed),
+            ("wall_slide_speed", self.wall_slide_speed),
+            ("wall_jump_push", self.wall_jump_push),
+            ("dash_distance", self.dash_distance),
+            ("dash_duration", self.dash_duration)
+        ];
+        let fractions = [
+            ("acceleration", self.acceleration),
+            ("deceleration", self.deceleration)
+        ];
+        let non_negative = [
+            ("jump_gravity_scale", self.jump_gravity_scale),
+            ("jump_hold_time", self.jump_hold_time),
+            ("jump_grace_period", self.jump_grace_period),
+            ("wall_jump_lock_time", self.wall_jump_lock_time),
+            ("dash_cooldown", self.dash_cooldown)
+        ];
+
+        for (name, value) in positive {
+            if !(value.is_finite() && value > 0.0) {
+                return Err(format!("{name} has to be positive, got {value}"));
+            }
+        }
+        for (name, value) in fractions {
+            if !(value > 0.0 && value <= 1.0) {
+                return Err(format!("{name} has to be in (0, 1], got {value}"));
+            }
+        }
+        for (name, value) in non_negative {
+            if !(value.is_finite() && value >= 0.0) {
+                return Err(format!("{name} can't be negative, got {value}"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Keeps the tuning asset loaded so it can be hot reloaded
+#[derive(Resource)]
+pub struct MovementTuningHandle(pub Handle<MovementTuning>);
+
+#[derive(Default)]
+pub struct MovementTuningLoader;
+
+#[derive(Debug, Error)]
+pub enum MovementTuningLoaderError {
+    #[error("Could not read the tuning file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse the tuning file: {0}")]
+    Ron(#[from] ron::error::SpannedError)
+}
+
+impl AssetLoader for MovementTuningLoader {
+    type Asset = MovementTuning;
+    type Settings = ();
+    type Error = MovementTuningLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext
+    ) -> BoxedFuture<'a, Result<MovementTuning, MovementTuningLoaderError>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            Ok(ron::de::from_bytes(&bytes)?)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["tuning.ron"]
+    }
+}
+
+pub fn load_movement_tuning(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(MovementTuningHandle(asset_server.load(MOVEMENT_TUNING_PATH)));
+}
+
+/// Copies the tuning asset into the `MovementTuning` resource whenever it is (re)loaded.
+/// Invalid values are logged and the previous ones are kept.
+pub fn apply_movement_tuning(
+    mut asset_events: EventReader<AssetEvent<MovementTuning>>,
+    tuning_assets: Res<Assets<MovementTuning>>,
+    mut tuning: ResMut<MovementTuning>
+) {
+    for event in asset_events.read() {
+        if let AssetEvent::Added { id } | AssetEvent::Modified { id } = event {
+            let Some(new_tuning) = tuning_assets.get(*id) else {
+                continue;
+            };
+
+            match new_tuning.validate() {
+                Ok(()) => {
+                    if *tuning != *new_tuning {
+                        info!("Applied movement tuning from {MOVEMENT_TUNING_PATH}");
+                        *tuning = new_tuning.clone();
+                    }
+                }
+                Err(error) => error!("Invalid movement tuning in {MOVEMENT_TUNING_PATH}, keeping the previous values: {error}")
+            }
+        }
+    }
+}
@@ -1,13 +1,57 @@
 use std::collections::HashMap;
 
-use bevy::{prelude::*, utils::HashSet};
+use bevy::{ecs::system::SystemParam, prelude::*, utils::HashSet};
 use bevy_ecs_ldtk::prelude::*;
-use bevy_rapier2d::prelude::*;
+use bevy_rapier2d::{prelude::*, rapier::math::Vector};
 
-use crate::{Spikes, Wall};
+use crate::{GameSet, GameSetsPlugin, Grappleable, GrappleableWallBundle, Hazard, JumpComponent, Ladder, LadderBundle, OneWayPlatform, OneWayPlatformBundle, ONE_WAY_TOP_TOLERANCE, Spikes, TileMaterial, TileMaterials, Wall, WallBundle};
+
+/// How far from straight up a contact normal can be for a body to land on a one-way platform, in radians
+const ONE_WAY_ALLOWED_ANGLE : f32 = 0.1;
+
+/// Turns wall, ladder, spike and one-way platform IntGrid tiles into merged rectangle colliders
+pub struct LevelCollisionPlugin;
+
+/// Identifiers of the IntGrid layers whose tiles get colliders, `Walls` and `Walls2` by default
+#[derive(Resource, Clone, Debug)]
+pub struct CollisionLayers(pub Vec<String>);
+
+impl Default for CollisionLayers {
+    fn default() -> Self {
+        CollisionLayers(vec!["Walls".to_string(), "Walls2".to_string()])
+    }
+}
+
+impl CollisionLayers {
+    pub fn contains(&self, identifier: &str) -> bool {
+        self.0.iter().any(|layer| layer == identifier)
+    }
+}
+
+impl Plugin for LevelCollisionPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<GameSetsPlugin>() {
+            app.add_plugins(GameSetsPlugin);
+        }
+
+        app.register_ldtk_int_cell::<WallBundle>(1)
+            // The grapple bites into stone
+            .register_ldtk_int_cell::<GrappleableWallBundle>(2)
+            .register_ldtk_int_cell::<WallBundle>(6)
+            .register_ldtk_int_cell::<WallBundle>(7)
+            .register_ldtk_int_cell::<LadderBundle>(3)
+            .register_ldtk_int_cell::<OneWayPlatformBundle>(5)
+            .init_resource::<CollisionLayers>()
+            .init_resource::<TileMaterials>()
+            .add_systems(FixedUpdate, spawn_wall_collision.in_set(GameSet::PhysicsPrep));
+    }
+}
 
 /// Spawns heron collisions for the walls of a level
 ///
+/// Only tiles on the layers in `CollisionLayers` get colliders,
+/// and tiles are only ever merged with tiles of the same layer.
+///
 /// You could just insert a ColliderBundle in to the WallBundle,
 /// but this spawns a different collider for EVERY wall tile.
 /// This approach leads to bad performance.
@@ -24,61 +68,177 @@ use crate::{Spikes, Wall};
 /// 4. spawn colliders for each rectangle
 pub fn spawn_wall_collision(
     mut commands: Commands,
-    wall_query: Query<(&GridCoords, &Parent), Added<Wall>>,
+    wall_query: Query<(&GridCoords, &Parent, &IntGridCell, Has<Grappleable>), Added<Wall>>,
     spikes_query: Query<(&GridCoords, &Parent), Added<Spikes>>,
-    parent_query: Query<&Parent, Without<Wall>>,
-    level_query: Query<(Entity, &LevelIid)>,
-    ldtk_projects: Query<&Handle<LdtkProject>>,
-    ldtk_project_assets: Res<Assets<LdtkProject>>,
+    ladder_query: Query<(&GridCoords, &Parent), Added<Ladder>>,
+    one_way_platform_query: Query<(&GridCoords, &Parent), Added<OneWayPlatform>>,
+    layer_query: Query<(&LayerMetadata, &Parent)>,
+    collision_layers: Res<CollisionLayers>,
+    tile_materials: Res<TileMaterials>
 ) {
+    // Walls are merged separately for every material, so each rectangle gets the friction and restitution of its tiles,
+    // and grappleable walls separately from the others
+    let mut material_to_walls: HashMap<(&'static str, bool), (TileMaterial, Vec<(GridCoords, Entity)>)> = HashMap::new();
+    for (&grid_coords, parent, int_grid_cell, grappleable) in &wall_query {
+        let material = tile_materials.get(int_grid_cell.value);
+        material_to_walls
+            .entry((material.name, grappleable))
+            .or_insert_with(|| (material, Vec::new()))
+            .1
+            .push((grid_coords, parent.get()));
+    }
+    for ((_, grappleable), (material, walls)) in material_to_walls {
+        let physics_material = (material, Restitution::new(material.restitution));
+        if grappleable {
+            spawn_collisions_internal(&mut commands, walls, &layer_query, &collision_layers, (physics_material, Grappleable));
+        } else {
+            spawn_collisions_internal(&mut commands, walls, &layer_query, &collision_layers, physics_material);
+        }
+    }
+
+    spawn_collisions_internal(&mut commands, tiles_of(&spikes_query), &layer_query, &collision_layers, (Spikes, Hazard::default()));
+    // Ladders only need to be detected, so they become sensors the player can move through
+    spawn_collisions_internal(&mut commands, tiles_of(&ladder_query), &layer_query, &collision_layers, (Ladder, Sensor));
+    // One-way platforms decide which contacts to keep in OneWayPlatformHooks
+    spawn_collisions_internal(&mut commands, tiles_of(&one_way_platform_query), &layer_query, &collision_layers,
+        (OneWayPlatform, ActiveHooks::MODIFY_SOLVER_CONTACTS));
+}
 
-    let mut level_to_spikes_locations: HashMap<Entity, HashSet<GridCoords>> = HashMap::new();
+/// Grid coordinates of the tiles and the layer entities they belong to
+fn tiles_of<T: Component>(tile_query: &Query<(&GridCoords, &Parent), Added<T>>) -> Vec<(GridCoords, Entity)> {
+    tile_query.iter().map(|(&grid_coords, parent)| (grid_coords, parent.get())).collect()
+}
 
+/// Rapier physics hooks that let bodies pass through `OneWayPlatform`s from below,
+/// and let a player dropping through one fall through it from above.
+///
+/// A player whose feet are already below the top of the platform keeps passing through,
+/// so a drop through a full tile doesn't get pushed back up once `JumpComponent::dropping_through` ends.
+#[derive(SystemParam)]
+pub struct OneWayPlatformHooks<'w, 's> {
+    platforms: Query<'w, 's, (&'static GlobalTransform, &'static Collider), With<OneWayPlatform>>,
+    jumpers: Query<'w, 's, (&'static JumpComponent, &'static Transform, &'static Collider)>,
+    time: Res<'w, Time>
+}
 
+fn half_height(collider: &Collider) -> f32 {
+    collider.as_cuboid().map_or(0.0, |cuboid| cuboid.half_extents().y)
+}
 
-    spikes_query.for_each(|(&grid_coords, parent)| {
-        if let Ok(grandparent) = parent_query.get(parent.get()) {
-            level_to_spikes_locations
-                .entry(grandparent.get())
-                .or_default()
-                .insert(grid_coords);
+impl BevyPhysicsHooks for OneWayPlatformHooks<'_, '_> {
+    fn modify_solver_contacts(&self, context: ContactModificationContextView) {
+        let (allowed_normal, (platform_transform, platform_collider), other) = if let Ok(platform) = self.platforms.get(context.collider1()) {
+            (Vector::y(), platform, context.collider2())
+        } else if let Ok(platform) = self.platforms.get(context.collider2()) {
+            // The normal points from the first collider to the second one, so from the body down to the platform
+            (-Vector::y(), platform, context.collider1())
+        } else {
+            return;
+        };
+
+        let top = platform_transform.translation().y + half_height(platform_collider);
+        let passing_through = self.jumpers.get(other).is_ok_and(|(jump_component, transform, collider)| {
+            let feet = transform.translation.y - half_height(collider);
+            jump_component.dropping_through(self.time.elapsed_seconds()) || feet < top - ONE_WAY_TOP_TOLERANCE
+        });
+        if passing_through {
+            context.raw.solver_contacts.clear();
+        } else {
+            context.raw.update_as_oneway_platform(&allowed_normal, ONE_WAY_ALLOWED_ANGLE);
         }
-    });
-    
-    spawn_collisions_internal(&mut commands, wall_query, &parent_query, &level_query, &ldtk_projects, &ldtk_project_assets, ());
-    spawn_collisions_internal(&mut commands, spikes_query, &parent_query, &level_query, &ldtk_projects, &ldtk_project_assets, Spikes);
+    }
 }
 
-fn spawn_collisions_internal<T: bevy::prelude::Component>(
+fn spawn_collisions_internal(
     commands: &mut Commands,
-    tile_query: Query<(&GridCoords, &Parent), Added<T>>,
-    parent_query: &Query<&Parent, Without<Wall>>,
-    level_query: &Query<(Entity, &LevelIid)>,
-    ldtk_projects: &Query<&Handle<LdtkProject>>,
-    ldtk_project_assets: &Res<Assets<LdtkProject>>,
+    tiles: Vec<(GridCoords, Entity)>,
+    layer_query: &Query<(&LayerMetadata, &Parent)>,
+    collision_layers: &CollisionLayers,
     bundle_to_add: impl Bundle + Copy
 ) {
-    // Consider where the walls are
+    // Consider where the tiles are
     // storing them as GridCoords in a HashSet for quick, easy lookup
     //
-    // The key of this map will be the entity of the level the wall belongs to.
+    // The key of this map will be the entity of the layer the tile belongs to, which is its direct parent.
     // This has two consequences in the resulting collision entities:
-    // 1. it forces the walls to be split along level boundaries
-    // 2. it lets us easily add the collision entities as children of the appropriate level entity
-    let mut level_to_wall_locations: HashMap<Entity, HashSet<GridCoords>> = HashMap::new();
-
-    tile_query.for_each(|(&grid_coords, parent)| {
-        // An intgrid tile's direct parent will be a layer entity, not the level entity
-        // To get the level entity, you need the tile's grandparent.
-        // This is where parent_query comes in.
-        if let Ok(grandparent) = parent_query.get(parent.get()) {
-            level_to_wall_locations
-                .entry(grandparent.get())
-                .or_default()
-                .insert(grid_coords);
+    // 1. tiles are only merged with tiles of the same layer, which also splits them along level boundaries
+    // 2. every rectangle is sized and placed with the grid size and offset of its own layer
+    let mut layer_to_tile_locations: HashMap<Entity, HashSet<GridCoords>> = HashMap::new();
+
+    for (grid_coords, layer_entity) in tiles {
+        layer_to_tile_locations
+            .entry(layer_entity)
+            .or_default()
+            .insert(grid_coords);
+    }
+
+    for (layer_entity, tiles) in layer_to_tile_locations {
+        // The layer's parent is the level entity
+        let Ok((layer, level_entity)) = layer_query.get(layer_entity) else {
+            continue;
+        };
+
+        if !collision_layers.contains(&layer.identifier) {
+            continue;
         }
-    });
 
+        let grid_size = layer.grid_size as f32;
+        // LDtk's y axis points down
+        let layer_offset = Vec2::new(layer.px_total_offset_x as f32, -layer.px_total_offset_y as f32);
+        let rects = merge_tiles_into_rects(&tiles, layer.c_wid, layer.c_hei);
+
+        commands.entity(level_entity.get()).with_children(|level| {
+            // Spawn colliders for every rectangle..
+            // Making the collider a child of the level serves two purposes:
+            // 1. Adjusts the transforms to be relative to the level for free
+            // 2. the colliders will be despawned automatically when levels unload
+            for rect in rects {
+                let half_extents = rect.half_extents(grid_size);
+                let center = layer_offset + rect.center(grid_size);
+
+                level
+                    .spawn_empty()
+                    .insert(Collider::cuboid(half_extents.x, half_extents.y))
+                    .insert(RigidBody::Fixed)
+                    .insert(Friction::new(1.0))
+                    .insert(Transform::from_xyz(center.x, center.y, 0.))
+                    .insert(GlobalTransform::default())
+                    .insert(bundle_to_add);
+            }
+        });
+    }
+}
+
+/// A rectangle of tiles in grid coordinates, all sides inclusive
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct TileRect {
+    pub left: i32,
+    pub right: i32,
+    pub top: i32,
+    pub bottom: i32,
+}
+
+impl TileRect {
+    /// Half the size of the rectangle for tiles of `grid_size` pixels
+    pub fn half_extents(&self, grid_size: f32) -> Vec2 {
+        Vec2::new(
+            (self.right - self.left + 1) as f32 * grid_size / 2.,
+            (self.top - self.bottom + 1) as f32 * grid_size / 2.
+        )
+    }
+
+    /// Center of the rectangle relative to the bottom left corner of its layer
+    pub fn center(&self, grid_size: f32) -> Vec2 {
+        Vec2::new(
+            (self.left + self.right + 1) as f32 * grid_size / 2.,
+            (self.bottom + self.top + 1) as f32 * grid_size / 2.
+        )
+    }
+}
+
+/// Merges the tiles of a `width` x `height` grid into rectangles that cover them exactly, without overlaps.
+/// Tiles outside of the grid are ignored.
+pub fn merge_tiles_into_rects(tiles: &HashSet<GridCoords>, width: i32, height: i32) -> Vec<TileRect> {
     /// Represents a wide wall that is 1 tile tall
     /// Used to spawn wall collisions
     #[derive(Clone, Eq, PartialEq, Debug, Default, Hash)]
@@ -87,119 +247,61 @@ fn spawn_collisions_internal<T: bevy::prelude::Component>(
         right: i32,
     }
 
-    /// A simple rectangle type representing a wall of any size
-    struct Rect {
-        left: i32,
-        right: i32,
-        top: i32,
-        bottom: i32,
-    }
+    // combine wall tiles into flat "plates" in each individual row
+    let mut plate_stack: Vec<Vec<Plate>> = Vec::new();
 
-    if !tile_query.is_empty() {
-        level_query.for_each(|(level_entity, level_iid)| {
-            if let Some(level_walls) = level_to_wall_locations.get(&level_entity) {
-                let ldtk_project = ldtk_project_assets
-                    .get(ldtk_projects.single())
-                    .expect("Project should be loaded if level has spawned");
-
-                let level = ldtk_project
-                    .as_standalone()
-                    .get_loaded_level_by_iid(&level_iid.to_string())
-                    .expect("Spawned level should exist in LDtk project");
-
-                let LayerInstance {
-                    c_wid: width,
-                    c_hei: height,
-                    grid_size,
-                    ..
-                } = level.layer_instances()[0];
-
-                // combine wall tiles into flat "plates" in each individual row
-                let mut plate_stack: Vec<Vec<Plate>> = Vec::new();
-
-                for y in 0..height {
-                    let mut row_plates: Vec<Plate> = Vec::new();
-                    let mut plate_start = None;
-
-                    // + 1 to the width so the algorithm "terminates" plates that touch the right edge
-                    for x in 0..width + 1 {
-                        match (plate_start, level_walls.contains(&GridCoords { x, y })) {
-                            (Some(s), false) => {
-                                row_plates.push(Plate {
-                                    left: s,
-                                    right: x - 1,
-                                });
-                                plate_start = None;
-                            }
-                            (None, true) => plate_start = Some(x),
-                            _ => (),
-                        }
-                    }
-
-                    plate_stack.push(row_plates);
-                }
+    for y in 0..height {
+        let mut row_plates: Vec<Plate> = Vec::new();
+        let mut plate_start = None;
 
-                // combine "plates" into rectangles across multiple rows
-                let mut rect_builder: HashMap<Plate, Rect> = HashMap::new();
-                let mut prev_row: Vec<Plate> = Vec::new();
-                let mut wall_rects: Vec<Rect> = Vec::new();
-
-                // an extra empty row so the algorithm "finishes" the rects that touch the top edge
-                plate_stack.push(Vec::new());
-
-                for (y, current_row) in plate_stack.into_iter().enumerate() {
-                    for prev_plate in &prev_row {
-                        if !current_row.contains(prev_plate) {
-                            // remove the finished rect so that the same plate in the future starts a new rect
-                            if let Some(rect) = rect_builder.remove(prev_plate) {
-                                wall_rects.push(rect);
-                            }
-                        }
-                    }
-                    for plate in &current_row {
-                        rect_builder
-                            .entry(plate.clone())
-                            .and_modify(|e| e.top += 1)
-                            .or_insert(Rect {
-                                bottom: y as i32,
-                                top: y as i32,
-                                left: plate.left,
-                                right: plate.right,
-                            });
-                    }
-                    prev_row = current_row;
+        // + 1 to the width so the algorithm "terminates" plates that touch the right edge
+        for x in 0..width + 1 {
+            match (plate_start, x < width && tiles.contains(&GridCoords { x, y })) {
+                (Some(s), false) => {
+                    row_plates.push(Plate {
+                        left: s,
+                        right: x - 1,
+                    });
+                    plate_start = None;
                 }
+                (None, true) => plate_start = Some(x),
+                _ => (),
+            }
+        }
 
-                commands.entity(level_entity).with_children(|level| {
-                    // Spawn colliders for every rectangle..
-                    // Making the collider a child of the level serves two purposes:
-                    // 1. Adjusts the transforms to be relative to the level for free
-                    // 2. the colliders will be despawned automatically when levels unload
-                    for wall_rect in wall_rects {
-                        level
-                            .spawn_empty()
-                            .insert(Collider::cuboid(
-                                (wall_rect.right as f32 - wall_rect.left as f32 + 1.)
-                                    * grid_size as f32
-                                    / 2.,
-                                (wall_rect.top as f32 - wall_rect.bottom as f32 + 1.)
-                                    * grid_size as f32
-                                    / 2.,
-                            ))
-                            .insert(RigidBody::Fixed)
-                            .insert(Friction::new(1.0))
-                            .insert(Transform::from_xyz(
-                                (wall_rect.left + wall_rect.right + 1) as f32 * grid_size as f32
-                                    / 2.,
-                                (wall_rect.bottom + wall_rect.top + 1) as f32 * grid_size as f32
-                                    / 2.,
-                                0.,
-                            ))
-                            .insert(GlobalTransform::default())
-                            .insert(bundle_to_add);
-                    }
-                });
+        plate_stack.push(row_plates);
+    }
+
+    // combine "plates" into rectangles across multiple rows
+    let mut rect_builder: HashMap<Plate, TileRect> = HashMap::new();
+    let mut prev_row: Vec<Plate> = Vec::new();
+    let mut wall_rects: Vec<TileRect> = Vec::new();
+
+    // an extra empty row so the algorithm "finishes" the rects that touch the top edge
+    plate_stack.push(Vec::new());
+
+    for (y, current_row) in plate_stack.into_iter().enumerate() {
+        for prev_plate in &prev_row {
+            if !current_row.contains(prev_plate) {
+                // remove the finished rect so that the same plate in the future starts a new rect
+                if let Some(rect) = rect_builder.remove(prev_plate) {
+                    wall_rects.push(rect);
+                }
             }
-        });
+        }
+        for plate in &current_row {
+            rect_builder
+                .entry(plate.clone())
+                .and_modify(|e| e.top += 1)
+                .or_insert(TileRect {
+                    bottom: y as i32,
+                    top: y as i32,
+                    left: plate.left,
+                    right: plate.right,
+                });
+        }
+        prev_row = current_row;
     }
+
+    wall_rects
 }
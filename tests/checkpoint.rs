@@ -0,0 +1,63 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevygame::{ActiveCheckpoint, Damage, LevelCompleted, PendingLevelTransfer};
+use common::*;
+
+/// Center of the checkpoint in the first level, between the spawn and the spring
+const CHECKPOINT_POSITION : Vec2 = Vec2::new(152.0, 40.0);
+
+/// The level right of the first one
+const SECOND_LEVEL_IID : &str = "fe96b930-b0a0-11ee-bd40-ab8946e60376";
+
+fn active_checkpoint(harness: &TestHarness) -> ActiveCheckpoint {
+    harness.app.world.resource::<ActiveCheckpoint>().clone()
+}
+
+#[test]
+fn touching_a_checkpoint_activates_it() {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+    assert_eq!(active_checkpoint(&harness), ActiveCheckpoint::default());
+
+    harness.player_mut::<Transform>().translation.x = CHECKPOINT_POSITION.x;
+    harness.tick_until(30, |harness| active_checkpoint(harness).0.is_some());
+
+    let checkpoint = active_checkpoint(&harness).0.unwrap();
+    assert_eq!(checkpoint.level, FIRST_LEVEL_IID);
+    assert!(checkpoint.position.distance(CHECKPOINT_POSITION) < 1.0, "Checkpoint at {}", checkpoint.position);
+}
+
+#[test]
+fn player_respawns_at_the_active_checkpoint() {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+    harness.player_mut::<Transform>().translation.x = CHECKPOINT_POSITION.x;
+    harness.tick_until(30, |harness| active_checkpoint(harness).0.is_some());
+
+    // Walk away from it before dying
+    harness.player_mut::<Transform>().translation.x = 60.0;
+    harness.tick(1);
+    let player = harness.player();
+    harness.app.world.send_event(Damage { target: player, source: player, amount: 1, knockback: Vec2::Y });
+    harness.tick_until(10, TestHarness::dead);
+    harness.tick_until(120, |harness| !harness.dead());
+
+    assert!((harness.transform().translation.x - CHECKPOINT_POSITION.x).abs() < 1.0);
+}
+
+#[test]
+fn checkpoint_stays_active_in_other_levels_until_a_level_is_completed() {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+    harness.player_mut::<Transform>().translation.x = CHECKPOINT_POSITION.x;
+    harness.tick_until(30, |harness| active_checkpoint(harness).0.is_some());
+    let checkpoint = active_checkpoint(&harness);
+
+    harness.app.insert_resource(PendingLevelTransfer::new(LevelIid::new(SECOND_LEVEL_IID)));
+    harness.tick_until(600, |harness| harness.transform().translation.x > 512.0);
+    harness.tick(1);
+    assert_eq!(active_checkpoint(&harness), checkpoint);
+
+    harness.app.world.send_event(LevelCompleted { level: LevelIid::new(SECOND_LEVEL_IID), time: 10.0 });
+    harness.tick(1);
+    assert_eq!(active_checkpoint(&harness), ActiveCheckpoint::default());
+}
@@ -0,0 +1,180 @@
+#![allow(dead_code)]
+
+use std::time::Duration;
+
+use bevy::{app::PluginsState, prelude::*, time::TimeUpdateStrategy};
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+use bevygame::{headless_app, Action, Dead, JumpComponent, LatchedInput, Player, TickInput};
+
+/// Level_0, the only level with a `Player` entity
+pub const FIRST_LEVEL_IID : &str = "0f72e230-b0a0-11ee-851b-03ba2455339d";
+
+/// Length of a simulated frame, one fixed tick
+pub const FRAME_TIME : f32 = 1.0 / 60.0;
+
+/// Half the height of the player's collider, from its center down to its feet
+pub const PLAYER_HALF_HEIGHT : f32 = 14.0;
+
+/// Half the height of the platforms spawned by `TestHarness::spawn_platform`
+pub const PLATFORM_HALF_HEIGHT : f32 = 4.0;
+
+/// How many frames to wait for the level to load and the player to land before giving up
+const MAX_LOADING_FRAMES : usize = 2000;
+
+/// Runs the headless game frame by frame with scripted input
+pub struct TestHarness {
+    pub app: App
+}
+
+impl TestHarness {
+    /// Boots the given level and waits until the player has landed on the ground
+    pub fn new(level_iid: &str) -> Self {
+        let mut app = headless_app(LevelSelection::iid(level_iid));
+        // Exactly one fixed tick per frame
+        let timestep = app.world.resource::<Time<Fixed>>().timestep();
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(timestep));
+
+        // What App::run would do before the first update
+        while app.plugins_state() == PluginsState::Adding {
+            bevy::tasks::tick_global_task_pools_on_main_thread();
+        }
+        app.finish();
+        app.cleanup();
+
+        let mut harness = TestHarness { app };
+        harness.wait_until_on_ground();
+        harness
+    }
+
+    /// Updates until the player stands on the ground.
+    /// Loading is asynchronous, so frames are given a moment for the asset IO to make progress.
+    pub fn wait_until_on_ground(&mut self) {
+        for _ in 0..MAX_LOADING_FRAMES {
+            self.tick(1);
+
+            if self.try_player().is_some() && self.jump().on_ground {
+                return;
+            }
+
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        panic!("Player didn't land within {MAX_LOADING_FRAMES} frames");
+    }
+
+    /// Advances the simulation by the given number of frames.
+    /// Presses and releases only count as "just" pressed or released for the first frame.
+    pub fn tick(&mut self, frames: usize) {
+        for _ in 0..frames {
+            self.app.update();
+            self.app.world.resource_mut::<ButtonInput<KeyCode>>().clear();
+            self.app.world.resource_mut::<ButtonInput<MouseButton>>().clear();
+        }
+    }
+
+    /// Advances frame by frame until the condition holds, returning the number of frames it took
+    pub fn tick_until(&mut self, max_frames: usize, mut condition: impl FnMut(&mut Self) -> bool) -> usize {
+        for frame in 0..max_frames {
+            if condition(self) {
+                return frame;
+            }
+            self.tick(1);
+        }
+
+        panic!("Condition wasn't met within {max_frames} frames");
+    }
+
+    /// Runs one tick with just the given actions held instead of the input of the devices.
+    /// `aim` is where the grapple is aimed if it is pressed.
+    pub fn play(&mut self, actions: &[Action], aim: Option<Vec2>) {
+        let buttons = actions.iter().fold(0, |buttons, action| buttons | action.bit());
+        self.app.world.resource_mut::<LatchedInput>().replayed = Some(TickInput { buttons, aim });
+        self.tick(1);
+    }
+
+    pub fn press(&mut self, key: KeyCode) {
+        self.app.world.resource_mut::<ButtonInput<KeyCode>>().press(key);
+    }
+
+    pub fn release(&mut self, key: KeyCode) {
+        self.app.world.resource_mut::<ButtonInput<KeyCode>>().release(key);
+    }
+
+    pub fn press_mouse(&mut self, button: MouseButton) {
+        self.app.world.resource_mut::<ButtonInput<MouseButton>>().press(button);
+    }
+
+    pub fn release_mouse(&mut self, button: MouseButton) {
+        self.app.world.resource_mut::<ButtonInput<MouseButton>>().release(button);
+    }
+
+    pub fn try_player(&mut self) -> Option<Entity> {
+        self.app.world.query_filtered::<Entity, With<Player>>().get_single(&self.app.world).ok()
+    }
+
+    pub fn player(&mut self) -> Entity {
+        self.try_player().expect("Player should be spawned")
+    }
+
+    pub fn transform(&mut self) -> Transform {
+        let player = self.player();
+        *self.app.world.get::<Transform>(player).expect("Player should have a Transform")
+    }
+
+    pub fn velocity(&mut self) -> Velocity {
+        let player = self.player();
+        *self.app.world.get::<Velocity>(player).expect("Player should have a Velocity")
+    }
+
+    pub fn jump(&mut self) -> JumpComponent {
+        let player = self.player();
+        self.app.world.get::<JumpComponent>(player).expect("Player should have a JumpComponent").clone()
+    }
+
+    pub fn gravity_scale(&mut self) -> f32 {
+        let player = self.player();
+        self.app.world.get::<GravityScale>(player).expect("Player should have a GravityScale").0
+    }
+
+    pub fn player_mut<T: Component>(&mut self) -> Mut<T> {
+        let player = self.player();
+        self.app.world.get_mut::<T>(player).expect("Player should have the component")
+    }
+
+    pub fn dead(&mut self) -> bool {
+        let player = self.player();
+        self.app.world.get::<Dead>(player).is_some()
+    }
+
+    /// Moves the player up and lets it fall from there
+    pub fn lift_player(&mut self, height: f32) {
+        let mut transform = self.player_mut::<Transform>();
+        transform.translation.y += height;
+        *self.player_mut::<Velocity>() = Velocity::zero();
+    }
+
+    /// Height of the bottom of the player's collider
+    pub fn feet(&mut self) -> f32 {
+        self.transform().translation.y - PLAYER_HALF_HEIGHT
+    }
+
+    /// Spawns a thin platform with the given top center, along with the body and components to give it
+    pub fn spawn_platform(&mut self, top: Vec2, half_width: f32, bundle: impl Bundle) -> Entity {
+        self.app.world.spawn((
+            TransformBundle::from_transform(Transform::from_translation((top - Vec2::Y * PLATFORM_HALF_HEIGHT).extend(0.0))),
+            Collider::cuboid(half_width, PLATFORM_HALF_HEIGHT),
+            bundle
+        )).id()
+    }
+
+    /// Puts the player at rest just above the given position of their feet and waits until they have landed
+    pub fn drop_player_at(&mut self, feet: Vec2) {
+        let mut transform = self.player_mut::<Transform>();
+        transform.translation.x = feet.x;
+        transform.translation.y = feet.y + PLAYER_HALF_HEIGHT + 1.0;
+        *self.player_mut::<Velocity>() = Velocity::zero();
+        self.tick(1);
+        self.tick_until(120, |harness| harness.jump().on_ground);
+    }
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+use bevygame::{GameState, Goal, LevelSelectScreen, LevelTimer, PendingLevelTransfer, SaveData, SavePlugin};
+use common::{TestHarness, FIRST_LEVEL_IID};
+
+fn set_state(harness: &mut TestHarness, state: GameState) {
+    harness.app.world.resource_mut::<NextState<GameState>>().set(state);
+    harness.tick(1);
+}
+
+#[test]
+fn pausing_freezes_the_player() {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+    harness.lift_player(30.0);
+    set_state(&mut harness, GameState::Paused);
+
+    let paused_at = harness.transform().translation;
+    harness.press(KeyCode::KeyD);
+    harness.tick(30);
+    assert_eq!(harness.transform().translation, paused_at);
+
+    set_state(&mut harness, GameState::Playing);
+    harness.tick(10);
+    assert!(harness.transform().translation.y < paused_at.y, "The player should fall again after resuming");
+}
+
+#[test]
+fn time_spent_paused_doesnt_count_towards_the_best_time() {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+    harness.app.add_plugins(SavePlugin { save_path: None });
+    let now = harness.app.world.resource::<Time<Fixed>>().elapsed_seconds();
+    harness.app.world.resource_mut::<LevelTimer>().started_at = now;
+
+    harness.tick(30);
+    set_state(&mut harness, GameState::Paused);
+    harness.tick(600);
+    set_state(&mut harness, GameState::Playing);
+
+    let position = harness.transform().translation;
+    harness.app.world.spawn((TransformBundle::from_transform(Transform::from_translation(position)), Goal, Collider::cuboid(8.0, 8.0), Sensor));
+    harness.tick_until(30, |harness| harness.app.world.resource::<SaveData>().best_times.contains_key(FIRST_LEVEL_IID));
+
+    let best_time = harness.app.world.resource::<SaveData>().best_times[FIRST_LEVEL_IID];
+    assert!(best_time < 1.5, "Recorded {best_time}s for about 0.6s of play and 10s paused");
+}
+
+#[test]
+fn main_menu_despawns_the_world_and_playing_spawns_it_again() {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+
+    set_state(&mut harness, GameState::MainMenu);
+    harness.tick(1);
+    assert!(harness.try_player().is_none());
+
+    set_state(&mut harness, GameState::Playing);
+    harness.wait_until_on_ground();
+}
+
+#[test]
+fn level_select_moves_with_the_menu_actions_and_confirms() {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+    set_state(&mut harness, GameState::LevelComplete);
+
+    let levels = vec![LevelIid::new(FIRST_LEVEL_IID), LevelIid::new("second"), LevelIid::new("third")];
+    let names = vec!["Level_0".to_string(), "Level_1".to_string(), "Level_2".to_string()];
+    harness.app.world.spawn((TextBundle::from_section("", default()), LevelSelectScreen { levels, names, selected: 0 }));
+
+    // Moving up from the first level wraps around to the last one
+    harness.press(KeyCode::ArrowUp);
+    harness.tick(1);
+    harness.release(KeyCode::ArrowUp);
+    harness.tick(1);
+    let selected = harness.app.world.query::<&LevelSelectScreen>().single(&harness.app.world).selected;
+    assert_eq!(selected, 2);
+
+    harness.press(KeyCode::Enter);
+    harness.tick(1);
+    let transfer = harness.app.world.get_resource::<PendingLevelTransfer>().expect("Confirming should start a transfer");
+    assert_eq!(transfer.level, LevelIid::new("third"));
+}
@@ -0,0 +1,163 @@
+mod common;
+
+use std::time::Duration;
+
+use bevy::{ecs::system::{RunSystemOnce, SystemState}, prelude::*};
+use bevy_rapier2d::{dynamics::RopeJointBuilder, prelude::*};
+use bevygame::{find_grapple_target, reel_grapple, swing_on_grapple, Action, Grapple, GrappleMiss, GrappleMissed, GrappleSettings, Grappleable, Hook, Player, PlayerActions, RopeSettings, TickInput};
+use common::*;
+
+/// Bottom of the stone block above the spawn of the first level
+const STONE_CEILING : f32 = 80.0;
+
+/// Boots the first level without its hook, so only the hooks a test places count
+fn harness_without_hooks() -> TestHarness {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+    let hooks: Vec<Entity> = harness.app.world.query_filtered::<Entity, With<Hook>>().iter(&harness.app.world).collect();
+    for hook in hooks {
+        harness.app.world.entity_mut(hook).despawn_recursive();
+    }
+    harness
+}
+
+fn spawn_hook(harness: &mut TestHarness, position: Vec2) {
+    let transform = Transform::from_translation(position.extend(0.0));
+    harness.app.world.spawn((Hook, TransformBundle { local: transform, global: GlobalTransform::from(transform) }));
+}
+
+fn find_target(harness: &mut TestHarness, from: Vec2, aim: Vec2, settings: &GrappleSettings) -> Option<Vec2> {
+    let mut state = SystemState::<(Query<&GlobalTransform, With<Hook>>, Query<(), With<Grappleable>>, Res<RapierContext>)>::new(&mut harness.app.world);
+    let (hooks, grappleable, rapier_context) = state.get(&harness.app.world);
+    find_grapple_target(from, aim, &hooks, &grappleable, &rapier_context, settings)
+}
+
+fn hooks_only() -> GrappleSettings {
+    GrappleSettings { grapple_surfaces: false, ..default() }
+}
+
+#[test]
+fn hooks_out_of_range_are_ignored() {
+    let mut harness = harness_without_hooks();
+    let from = harness.transform().translation.xy();
+    let settings = GrappleSettings { require_line_of_sight: false, ..hooks_only() };
+
+    let far_hook = from + Vec2::X * (settings.max_range + 10.0);
+    spawn_hook(&mut harness, far_hook);
+    assert_eq!(find_target(&mut harness, from, far_hook, &settings), None);
+
+    let near_hook = from + Vec2::X * (settings.max_range - 10.0);
+    spawn_hook(&mut harness, near_hook);
+    assert_eq!(find_target(&mut harness, from, far_hook, &settings), Some(near_hook));
+}
+
+#[test]
+fn hooks_behind_walls_are_ignored() {
+    let mut harness = harness_without_hooks();
+    let from = harness.transform().translation.xy();
+
+    // Above the stone block, the player stands under it
+    let hidden_hook = Vec2::new(from.x, STONE_CEILING + 60.0);
+    spawn_hook(&mut harness, hidden_hook);
+
+    assert_eq!(find_target(&mut harness, from, hidden_hook, &hooks_only()), None);
+    let settings = GrappleSettings { require_line_of_sight: false, ..hooks_only() };
+    assert_eq!(find_target(&mut harness, from, hidden_hook, &settings), Some(hidden_hook));
+}
+
+#[test]
+fn aiming_at_a_grappleable_wall_attaches_to_it() {
+    let mut harness = harness_without_hooks();
+    let from = harness.transform().translation.xy();
+    let aim = from + Vec2::Y * 20.0;
+
+    let hit_point = find_target(&mut harness, from, aim, &GrappleSettings::default()).expect("Stone should be grappleable");
+    assert!((hit_point - Vec2::new(from.x, STONE_CEILING)).length() < 0.5, "attached at {hit_point}");
+
+    assert_eq!(find_target(&mut harness, from, aim, &hooks_only()), None);
+}
+
+#[test]
+fn a_visible_hook_wins_over_a_grappleable_wall() {
+    let mut harness = harness_without_hooks();
+    let from = harness.transform().translation.xy();
+
+    let hook = from + Vec2::new(60.0, 10.0);
+    spawn_hook(&mut harness, hook);
+
+    assert_eq!(find_target(&mut harness, from, from + Vec2::Y * 20.0, &GrappleSettings::default()), Some(hook));
+}
+
+#[test]
+fn grappling_at_nothing_sends_a_miss() {
+    let mut harness = harness_without_hooks();
+    let from = harness.transform().translation.xy();
+    // Nothing is in reach to the right of the spawn
+    harness.app.world.resource_mut::<GrappleSettings>().max_range = 100.0;
+    let aim = from + Vec2::X * 40.0;
+
+    harness.play(&[Action::Grapple], Some(aim));
+
+    let events = harness.app.world.resource::<Events<GrappleMissed>>();
+    let misses: Vec<Vec2> = events.get_reader().read(events).map(|miss| miss.towards).collect();
+    assert_eq!(misses, vec![aim]);
+    assert!(harness.app.world.query::<&Grapple>().iter(&harness.app.world).next().is_none());
+    assert!(harness.app.world.query::<&GrappleMiss>().iter(&harness.app.world).next().is_some());
+}
+
+/// Just a player hanging from a rope, with the actions held for a second
+fn rope_world(actions: &[Action], player: Vec2, grapple: Vec2, rope_length: f32) -> World {
+    let mut world = World::new();
+
+    let mut player_actions = PlayerActions::default();
+    player_actions.apply(TickInput { buttons: actions.iter().fold(0, |buttons, action| buttons | action.bit()), aim: None });
+    world.insert_resource(player_actions);
+    world.insert_resource(RopeSettings::default());
+    let mut time = Time::<()>::default();
+    time.advance_by(Duration::from_secs(1));
+    world.insert_resource(time);
+
+    let player = world.spawn((Player, Transform::from_translation(player.extend(0.0)), Velocity::zero())).id();
+    world.spawn((
+        Grapple,
+        Transform::from_translation(grapple.extend(0.0)),
+        ImpulseJoint::new(player, RopeJointBuilder::new(rope_length).build())
+    ));
+    world
+}
+
+fn reeled_length(action: Action, rope_length: f32) -> f32 {
+    let mut world = rope_world(&[Action::Grapple, action], Vec2::ZERO, Vec2::Y * rope_length, rope_length);
+    world.run_system_once(reel_grapple);
+    world.query_filtered::<&ImpulseJoint, With<Grapple>>().single(&world).data.as_rope().unwrap().max_distance()
+}
+
+fn swing_velocity(actions: &[Action], grapple: Vec2) -> Vec2 {
+    let mut world = rope_world(actions, Vec2::ZERO, grapple, grapple.length());
+    world.run_system_once(swing_on_grapple);
+    world.query_filtered::<&Velocity, With<Player>>().single(&world).linvel
+}
+
+#[test]
+fn reeling_changes_the_rope_length_within_its_limits() {
+    let settings = RopeSettings::default();
+
+    assert_eq!(reeled_length(Action::ReelIn, 100.0), 100.0 - settings.reel_speed);
+    assert_eq!(reeled_length(Action::ReelOut, 60.0), 60.0 + settings.reel_speed);
+    assert_eq!(reeled_length(Action::ReelIn, settings.min_length + 5.0), settings.min_length);
+    assert_eq!(reeled_length(Action::ReelOut, settings.max_length - 5.0), settings.max_length);
+}
+
+#[test]
+fn moving_sideways_pushes_along_the_swing() {
+    let force = RopeSettings::default().swing_force;
+
+    // Hanging straight down, the swing is horizontal
+    assert_eq!(swing_velocity(&[Action::Grapple, Action::MoveRight], Vec2::Y * 100.0), Vec2::new(force, 0.0));
+    assert_eq!(swing_velocity(&[Action::Grapple, Action::MoveLeft], Vec2::Y * 100.0), Vec2::new(-force, 0.0));
+
+    // Hanging to the bottom left of the grapple, pushing right swings down along the arc
+    let velocity = swing_velocity(&[Action::Grapple, Action::MoveRight], Vec2::new(100.0, 100.0));
+    assert!((velocity - Vec2::new(1.0, -1.0).normalize() * force).length() < 0.001, "swung with {velocity}");
+
+    assert_eq!(swing_velocity(&[Action::Grapple], Vec2::Y * 100.0), Vec2::ZERO);
+}
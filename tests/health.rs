@@ -0,0 +1,79 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use bevygame::{Damage, Health, LevelRules, PLAYER_MAX_HEALTH};
+use common::*;
+
+/// Center of the spike tile in the first level, on a ledge right of the spring
+const SPIKES_POSITION : Vec2 = Vec2::new(232.0, 56.0);
+
+fn drop_onto_spikes(harness: &mut TestHarness) {
+    let mut transform = harness.player_mut::<Transform>();
+    transform.translation.x = SPIKES_POSITION.x;
+    transform.translation.y = SPIKES_POSITION.y + 8.0 + PLAYER_HALF_HEIGHT + 4.0;
+    *harness.player_mut::<Velocity>() = Velocity::zero();
+}
+
+fn health_based(harness: &mut TestHarness) {
+    harness.app.world.resource_mut::<LevelRules>().one_hit_kill = false;
+}
+
+fn damage(harness: &mut TestHarness, amount: u32) {
+    let player = harness.player();
+    harness.app.world.send_event(Damage { target: player, source: player, amount, knockback: Vec2::X });
+}
+
+#[test]
+fn levels_are_one_hit_kill_by_default() {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+    assert!(harness.app.world.resource::<LevelRules>().one_hit_kill);
+
+    drop_onto_spikes(&mut harness);
+    harness.tick_until(60, TestHarness::dead);
+
+    harness.tick_until(120, |harness| !harness.dead());
+    assert_eq!(harness.player_mut::<Health>().current, PLAYER_MAX_HEALTH);
+}
+
+#[test]
+fn spikes_take_health_and_knock_the_player_back() {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+    health_based(&mut harness);
+
+    drop_onto_spikes(&mut harness);
+    harness.tick_until(60, |harness| harness.player_mut::<Health>().current < PLAYER_MAX_HEALTH);
+
+    assert_eq!(harness.player_mut::<Health>().current, PLAYER_MAX_HEALTH - 1);
+    assert!(!harness.dead());
+    assert!(harness.velocity().linvel.y > 0.0);
+}
+
+#[test]
+fn damage_is_ignored_while_invulnerable() {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+    health_based(&mut harness);
+
+    damage(&mut harness, 1);
+    harness.tick(1);
+    damage(&mut harness, 1);
+    harness.tick(1);
+
+    assert_eq!(harness.player_mut::<Health>().current, PLAYER_MAX_HEALTH - 1);
+
+    harness.tick(90);
+    damage(&mut harness, 1);
+    harness.tick(1);
+    assert_eq!(harness.player_mut::<Health>().current, PLAYER_MAX_HEALTH - 2);
+}
+
+#[test]
+fn player_dies_when_health_runs_out() {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+    health_based(&mut harness);
+
+    damage(&mut harness, PLAYER_MAX_HEALTH);
+    harness.tick(2);
+
+    assert!(harness.dead());
+}
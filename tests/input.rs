@@ -0,0 +1,78 @@
+use bevy::{input::gamepad::{GamepadAxis, GamepadButton, Gamepads}, prelude::*};
+use bevygame::{Action, ActionInputPlugin, ActionState, Binding, InputBindings};
+
+fn input_app() -> App {
+    let mut app = App::new();
+    app.init_resource::<ButtonInput<KeyCode>>()
+        .init_resource::<ButtonInput<MouseButton>>()
+        .init_resource::<Gamepads>()
+        .init_resource::<ButtonInput<GamepadButton>>()
+        .init_resource::<Axis<GamepadAxis>>()
+        .add_plugins(ActionInputPlugin { bindings_path: None });
+    app
+}
+
+fn press(app: &mut App, key: KeyCode) {
+    app.world.resource_mut::<ButtonInput<KeyCode>>().press(key);
+}
+
+fn release(app: &mut App, key: KeyCode) {
+    app.world.resource_mut::<ButtonInput<KeyCode>>().release(key);
+}
+
+fn actions(app: &App) -> &ActionState {
+    app.world.resource::<ActionState>()
+}
+
+#[test]
+fn keys_trigger_their_actions() {
+    let mut app = input_app();
+
+    press(&mut app, KeyCode::Space);
+    app.update();
+    assert!(actions(&app).just_pressed(Action::Jump));
+    assert!(actions(&app).pressed(Action::Jump));
+    assert!(!actions(&app).pressed(Action::MoveLeft));
+
+    app.world.resource_mut::<ButtonInput<KeyCode>>().clear();
+    app.update();
+    assert!(!actions(&app).just_pressed(Action::Jump));
+    assert!(actions(&app).pressed(Action::Jump));
+
+    release(&mut app, KeyCode::Space);
+    app.update();
+    assert!(actions(&app).just_released(Action::Jump));
+    assert!(!actions(&app).pressed(Action::Jump));
+}
+
+#[test]
+fn one_key_can_trigger_several_actions() {
+    let mut app = input_app();
+
+    press(&mut app, KeyCode::KeyW);
+    app.update();
+    assert!(actions(&app).pressed(Action::MoveUp));
+    assert!(actions(&app).pressed(Action::ReelIn));
+}
+
+#[test]
+fn rebinding_replaces_the_old_bindings() {
+    let mut app = input_app();
+    app.world.resource_mut::<InputBindings>().rebind(Action::Jump, vec![Binding::Key(KeyCode::KeyK)]);
+
+    press(&mut app, KeyCode::Space);
+    app.update();
+    assert!(!actions(&app).pressed(Action::Jump));
+
+    press(&mut app, KeyCode::KeyK);
+    app.update();
+    assert!(actions(&app).just_pressed(Action::Jump));
+}
+
+#[test]
+fn bindings_survive_a_ron_round_trip() {
+    let bindings = InputBindings::default();
+    let text = ron::ser::to_string_pretty(&bindings, ron::ser::PrettyConfig::default()).unwrap();
+
+    assert_eq!(ron::from_str::<InputBindings>(&text).unwrap(), bindings);
+}
@@ -0,0 +1,97 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, time::TimeUpdateStrategy};
+use bevy_rapier2d::prelude::*;
+use bevygame::{InterpolationPlugin, Teleported};
+
+const TIMESTEP : Duration = Duration::from_millis(20);
+
+fn move_right(mut bodies: Query<&mut Transform, With<RigidBody>>) {
+    for mut transform in &mut bodies {
+        transform.translation.x += 1.0;
+    }
+}
+
+/// Where `teleport` moves the bodies on the next tick
+#[derive(Resource, Default)]
+struct TeleportTo(Option<f32>);
+
+fn teleport(mut commands: Commands, mut teleport_to: ResMut<TeleportTo>, mut bodies: Query<(Entity, &mut Transform), With<RigidBody>>) {
+    let Some(x) = teleport_to.0.take() else {
+        return;
+    };
+    for (entity, mut transform) in &mut bodies {
+        transform.translation.x = x;
+        commands.entity(entity).insert(Teleported);
+    }
+}
+
+/// Ticks twice as rarely as frames, moving the body by one unit each tick
+fn interpolation_app() -> (App, Entity) {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugins(InterpolationPlugin)
+        .insert_resource(Time::<Fixed>::from_duration(TIMESTEP))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(TIMESTEP / 2))
+        .init_resource::<TeleportTo>()
+        .add_systems(FixedUpdate, (move_right, teleport).chain());
+
+    let body = app.world.spawn((TransformBundle::default(), RigidBody::Dynamic)).id();
+    (app, body)
+}
+
+fn x(app: &App, body: Entity) -> f32 {
+    app.world.get::<Transform>(body).unwrap().translation.x
+}
+
+#[test]
+fn frames_between_ticks_are_interpolated() {
+    let (mut app, body) = interpolation_app();
+
+    // The first frame only starts the clock, the third one runs the first tick
+    for _ in 0..3 {
+        app.update();
+    }
+    assert_eq!(x(&app, body), 0.0);
+
+    app.update();
+    assert_eq!(x(&app, body), 0.5);
+
+    app.update();
+    assert_eq!(x(&app, body), 1.0);
+    app.update();
+    assert_eq!(x(&app, body), 1.5);
+}
+
+#[test]
+fn teleports_are_not_interpolated() {
+    let (mut app, body) = interpolation_app();
+    for _ in 0..4 {
+        app.update();
+    }
+
+    app.world.get_mut::<Transform>(body).unwrap().translation.x = 100.0;
+    app.update();
+    assert_eq!(x(&app, body), 100.0);
+    app.update();
+    assert_eq!(x(&app, body), 100.5);
+}
+
+#[test]
+fn teleports_during_a_tick_are_not_interpolated() {
+    let (mut app, body) = interpolation_app();
+    for _ in 0..4 {
+        app.update();
+    }
+
+    app.world.resource_mut::<TeleportTo>().0 = Some(100.0);
+    app.update();
+    assert_eq!(x(&app, body), 100.0);
+    app.update();
+    assert_eq!(x(&app, body), 100.0);
+    assert!(app.world.get::<Teleported>(body).is_none());
+
+    app.update();
+    app.update();
+    assert_eq!(x(&app, body), 100.5);
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use bevygame::{Footstep, TileMaterial, TileMaterials};
+use common::*;
+
+#[test]
+fn unknown_values_are_dirt() {
+    let materials = TileMaterials::default();
+
+    assert_eq!(materials.get(2), TileMaterial::STONE);
+    assert_eq!(materials.get(6), TileMaterial::ICE);
+    assert_eq!(materials.get(42), TileMaterial::DIRT);
+}
+
+#[test]
+fn ice_has_less_grip_and_sticky_is_slower() {
+    assert_eq!(TileMaterial::DIRT.grip(), 1.0);
+    assert_eq!(TileMaterial::DIRT.speed_factor(), 1.0);
+    assert!(TileMaterial::ICE.grip() < 0.1);
+    assert_eq!(TileMaterial::ICE.speed_factor(), 1.0);
+    assert_eq!(TileMaterial::STICKY.grip(), 1.0);
+    assert!(TileMaterial::STICKY.speed_factor() < 1.0);
+}
+
+fn drain_footsteps(harness: &mut TestHarness) -> Vec<Footstep> {
+    harness.app.world.resource_mut::<Events<Footstep>>().drain().collect()
+}
+
+#[test]
+fn walking_on_the_stone_floor_makes_stone_footsteps() {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+    drain_footsteps(&mut harness);
+
+    // The floor under the spawn is stone
+    harness.press(KeyCode::KeyD);
+    let mut footsteps = Vec::new();
+    for _ in 0..40 {
+        harness.tick(1);
+        footsteps.extend(drain_footsteps(&mut harness));
+    }
+
+    assert!(!footsteps.is_empty());
+    assert!(footsteps.iter().all(|footstep| footstep.material == TileMaterial::STONE));
+}
+
+/// Horizontal speed after running right for a few ticks
+fn speed_after_running(harness: &mut TestHarness) -> f32 {
+    harness.press(KeyCode::KeyD);
+    harness.tick(10);
+    harness.velocity().linvel.x
+}
+
+#[test]
+fn player_speeds_up_slower_on_ice() {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+    let ground_speed = speed_after_running(&mut harness);
+
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+    let top = Vec2::new(160.0, harness.feet() + 40.0);
+    harness.spawn_platform(top, 24.0, (RigidBody::Fixed, TileMaterial::ICE));
+    harness.drop_player_at(top);
+    assert_eq!(harness.jump().ground_material, Some(TileMaterial::ICE));
+
+    let ice_speed = speed_after_running(&mut harness);
+    assert!(ice_speed < ground_speed * 0.25, "{ice_speed} on ice, {ground_speed} on the ground");
+}
+
+#[test]
+fn the_material_under_most_of_the_feet_wins() {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+    let top = harness.feet() + 40.0;
+    // Ice from 136 to 184 next to sticky tiles from 184 to 200
+    for (x, half_width, material) in [(160.0, 24.0, TileMaterial::ICE), (192.0, 8.0, TileMaterial::STICKY)] {
+        harness.spawn_platform(Vec2::new(x, top), half_width, (RigidBody::Fixed, material));
+    }
+
+    harness.drop_player_at(Vec2::new(180.0, top));
+    assert_eq!(harness.jump().ground_material, Some(TileMaterial::ICE));
+
+    harness.player_mut::<Transform>().translation.x = 190.0;
+    harness.tick_until(10, |harness| harness.jump().ground_material == Some(TileMaterial::STICKY));
+}
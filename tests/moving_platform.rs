@@ -0,0 +1,70 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use bevygame::MovingPlatform;
+use common::*;
+
+fn waypoint_order(ping_pong: bool) -> Vec<usize> {
+    let mut platform = MovingPlatform::new(vec![Vec2::ZERO, Vec2::X, Vec2::Y], 10.0, ping_pong, 0.0);
+    (0..6).map(|_| {
+        platform.advance();
+        platform.target
+    }).collect()
+}
+
+#[test]
+fn looping_platform_goes_back_to_the_first_point() {
+    assert_eq!(waypoint_order(false), vec![1, 2, 0, 1, 2, 0]);
+}
+
+#[test]
+fn ping_pong_platform_turns_around_at_the_ends() {
+    assert_eq!(waypoint_order(true), vec![1, 2, 1, 0, 1, 2]);
+}
+
+/// Puts the player on a still platform above the ground right of the spawn
+fn harness_on_platform() -> (TestHarness, Entity) {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+    let top = Vec2::new(150.0, harness.feet() + 24.0);
+
+    let platform = harness.spawn_platform(top, 16.0, (
+        RigidBody::KinematicVelocityBased,
+        Velocity::zero(),
+        MovingPlatform::new(Vec::new(), 0.0, false, 0.0)
+    ));
+    harness.drop_player_at(top);
+
+    (harness, platform)
+}
+
+fn start_platform(harness: &mut TestHarness, platform: Entity, offset: Vec2, speed: f32) {
+    let start = harness.app.world.get::<Transform>(platform).unwrap().translation.xy();
+    *harness.app.world.get_mut::<MovingPlatform>(platform).unwrap() = MovingPlatform::new(vec![start, start + offset], speed, false, 0.0);
+}
+
+#[test]
+fn player_is_carried_along_by_the_platform() {
+    let (mut harness, platform) = harness_on_platform();
+    let player_start = harness.transform().translation.x;
+    let platform_start = harness.app.world.get::<Transform>(platform).unwrap().translation.x;
+
+    start_platform(&mut harness, platform, Vec2::new(-32.0, 0.0), 20.0);
+    harness.tick(60);
+
+    let player_moved = harness.transform().translation.x - player_start;
+    let platform_moved = harness.app.world.get::<Transform>(platform).unwrap().translation.x - platform_start;
+    assert!(platform_moved < -15.0, "Platform moved by {platform_moved}");
+    assert!((player_moved - platform_moved).abs() < 1.5, "Player moved by {player_moved}, platform by {platform_moved}");
+}
+
+#[test]
+fn player_stays_on_a_platform_moving_down() {
+    let (mut harness, platform) = harness_on_platform();
+
+    start_platform(&mut harness, platform, Vec2::new(0.0, -8.0), 8.0);
+    for _ in 0..60 {
+        harness.tick(1);
+        assert!(harness.jump().on_ground);
+    }
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+use bevygame::OneWayPlatform;
+use common::*;
+
+/// Row of the first level's `Walls` layer the platform is put in, counted from the bottom.
+/// The floor is the two rows below 0, so this leaves a gap of two tiles under the platform.
+const PLATFORM_ROW : i32 = 4;
+/// Columns of the platform, a gap in the level that is open above and below
+const PLATFORM_COLUMNS : std::ops::RangeInclusive<i32> = 9..=12;
+
+/// Adds one-way tiles to the first level's `Walls` layer, the way LDtk spawns IntGrid value 5,
+/// and puts the player on the ground under them. Returns the height of their top.
+fn harness_under_platform() -> (TestHarness, f32) {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+
+    let layer = harness.app.world.query::<(Entity, &LayerMetadata, &Parent)>()
+        .iter(&harness.app.world)
+        .find(|(_, layer, level)| {
+            layer.identifier == "Walls" && harness.app.world.get::<LevelIid>(level.get()).is_some_and(|iid| iid.get() == FIRST_LEVEL_IID)
+        })
+        .map(|(entity, _, _)| entity)
+        .expect("The first level should have a Walls layer");
+    for x in PLATFORM_COLUMNS {
+        harness.app.world.spawn((GridCoords::new(x, PLATFORM_ROW), IntGridCell { value: 5 }, OneWayPlatform)).set_parent(layer);
+    }
+
+    let feet = harness.feet();
+    harness.drop_player_at(Vec2::new((*PLATFORM_COLUMNS.start() as f32 + 2.0) * 16.0, feet));
+
+    let (transform, collider) = harness.app.world.query_filtered::<(&GlobalTransform, &Collider), With<OneWayPlatform>>()
+        .single(&harness.app.world);
+    let half_extents = collider.as_cuboid().expect("Platforms are cuboids").half_extents();
+    assert_eq!(half_extents.y, 8.0, "The platform should be a full tile tall");
+    let top = transform.translation().y + half_extents.y;
+
+    (harness, top)
+}
+
+/// Launches the player up through the platform and waits for them to land on it
+fn land_on_platform(harness: &mut TestHarness, top: f32) {
+    harness.player_mut::<Velocity>().linvel.y = 160.0;
+    harness.tick(1);
+    assert!(!harness.jump().on_ground);
+
+    harness.tick_until(300, |harness| harness.jump().on_ground && harness.velocity().linvel.y <= 0.0);
+    assert!((harness.feet() - top).abs() < 2.0, "Landed at {} instead of on the platform at {top}", harness.feet());
+}
+
+#[test]
+fn player_passes_through_from_below_and_lands_on_top() {
+    let (mut harness, top) = harness_under_platform();
+    land_on_platform(&mut harness, top);
+
+    let jump = harness.jump();
+    assert!(jump.on_one_way_platform);
+}
+
+#[test]
+fn down_and_jump_drops_through_the_platform() {
+    let (mut harness, top) = harness_under_platform();
+    land_on_platform(&mut harness, top);
+
+    harness.press(KeyCode::KeyS);
+    harness.press(KeyCode::Space);
+    harness.tick(1);
+    assert!(!harness.jump().jumping);
+
+    // All the way through the tile and down to the floor, without being pushed back up on the way
+    harness.tick_until(120, |harness| harness.feet() < top - 16.0 - PLAYER_HALF_HEIGHT * 2.0);
+    harness.tick_until(300, |harness| harness.jump().on_ground);
+    assert!(harness.feet() < top - 30.0);
+}
@@ -0,0 +1,277 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use bevygame::{Action, DashComponent, Hook, JumpComponent, MovementTuning, WallContact, JUMP_GRACE_PERIOD};
+use common::*;
+
+/// Highest point of a jump relative to the ground, holding space for the given number of frames
+fn jump_height(hold_frames: usize) -> f32 {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+    let ground_y = harness.transform().translation.y;
+
+    harness.press(KeyCode::Space);
+    harness.tick(hold_frames);
+    harness.release(KeyCode::Space);
+
+    let mut highest = harness.transform().translation.y;
+    harness.tick_until(600, |harness| {
+        highest = highest.max(harness.transform().translation.y);
+        harness.velocity().linvel.y < 0.0
+    });
+
+    highest - ground_y
+}
+
+#[test]
+fn jump_on_ground_starts_low_gravity_phase() {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+
+    harness.press(KeyCode::Space);
+    harness.tick(1);
+
+    let jump = harness.jump();
+    assert!(jump.jumping);
+    assert!(!jump.on_ground);
+    assert!(harness.velocity().linvel.y > 0.0);
+    assert_eq!(harness.gravity_scale(), 0.15);
+}
+
+#[test]
+fn jump_pressed_within_grace_period_before_landing_is_buffered() {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+    let ground_y = harness.transform().translation.y;
+
+    harness.lift_player(48.0);
+    harness.tick(1);
+
+    // Press space once the player is less than half of the grace period away from the ground
+    harness.tick_until(600, |harness| {
+        let distance = harness.transform().translation.y - ground_y;
+        let fall_speed = -harness.velocity().linvel.y;
+        fall_speed > 0.0 && distance < fall_speed * JUMP_GRACE_PERIOD / 2.0
+    });
+    harness.press(KeyCode::Space);
+    harness.tick(1);
+
+    harness.tick_until((JUMP_GRACE_PERIOD / FRAME_TIME) as usize + 2, |harness| harness.jump().jumping);
+    assert!(harness.velocity().linvel.y > 0.0);
+}
+
+#[test]
+fn jump_pressed_long_before_landing_is_ignored() {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+
+    harness.lift_player(48.0);
+    harness.tick(1);
+    harness.press(KeyCode::Space);
+    harness.tick(1);
+
+    harness.tick_until(600, |harness| harness.jump().on_ground);
+    harness.tick(2);
+
+    let jump = harness.jump();
+    assert!(!jump.jumping);
+    assert!(jump.on_ground);
+}
+
+#[test]
+fn releasing_space_early_ends_the_jump() {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+
+    harness.press(KeyCode::Space);
+    harness.tick(5);
+    assert!(harness.jump().jumping);
+
+    harness.release(KeyCode::Space);
+    harness.tick(1);
+
+    let jump = harness.jump();
+    assert!(!jump.jumping);
+    assert!(jump.falling);
+    assert_eq!(harness.gravity_scale(), 1.0);
+}
+
+#[test]
+fn releasing_space_early_jumps_lower() {
+    let short_hop = jump_height(3);
+    let full_jump = jump_height(30);
+
+    assert!(short_hop > 0.0);
+    assert!(short_hop < full_jump, "short hop {short_hop} should be lower than full jump {full_jump}");
+}
+
+#[test]
+fn landing_resets_gravity_scale() {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+
+    // Put the player mid air in the low gravity part of a jump
+    harness.lift_player(32.0);
+    harness.player_mut::<JumpComponent>().jumping = true;
+    *harness.player_mut::<GravityScale>() = GravityScale(0.15);
+    harness.tick(1);
+    assert_eq!(harness.gravity_scale(), 0.15);
+
+    harness.tick_until(1200, |harness| harness.jump().on_ground);
+    harness.tick(1);
+
+    let jump = harness.jump();
+    assert!(!jump.jumping);
+    assert!(!jump.falling);
+    assert_eq!(harness.gravity_scale(), 1.0);
+}
+
+/// Walks into the wall left of the spawn and lifts the player along it
+fn harness_against_left_wall() -> TestHarness {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+
+    harness.press(KeyCode::KeyA);
+    harness.tick_until(120, |harness| harness.jump().wall_contact == WallContact::Left);
+    harness.lift_player(32.0);
+    harness.tick(2);
+    assert_eq!(harness.jump().wall_contact, WallContact::Left);
+
+    harness
+}
+
+#[test]
+fn pressing_into_a_wall_caps_the_fall_speed() {
+    let mut harness = harness_against_left_wall();
+    let wall_slide_speed = MovementTuning::default().wall_slide_speed;
+
+    harness.tick_until(600, |harness| {
+        assert!(-harness.velocity().linvel.y <= wall_slide_speed + 0.01, "Fell at {} along the wall", -harness.velocity().linvel.y);
+        harness.jump().on_ground
+    });
+}
+
+#[test]
+fn wall_jump_pushes_away_from_the_wall() {
+    let mut harness = harness_against_left_wall();
+
+    harness.press(KeyCode::Space);
+    harness.tick(1);
+
+    let jump = harness.jump();
+    assert!(jump.jumping);
+    assert!(jump.input_locked_until.is_some());
+    assert!(harness.velocity().linvel.x > 0.0);
+    assert!(harness.velocity().linvel.y > 0.0);
+
+    // A is still held, but doesn't steer back into the wall during the lock
+    harness.tick(3);
+    assert!(harness.velocity().linvel.x > 0.0);
+}
+
+#[test]
+fn dash_covers_the_dash_distance_without_gravity() {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+    let tuning = MovementTuning::default();
+    harness.lift_player(16.0);
+    let start = harness.transform().translation;
+
+    harness.press(KeyCode::ShiftLeft);
+    harness.tick(1);
+    assert!(harness.player_mut::<DashComponent>().dashing());
+    harness.tick_until(60, |harness| !harness.player_mut::<DashComponent>().dashing());
+
+    let end = harness.transform().translation;
+    assert!((end.x - start.x - tuning.dash_distance).abs() < 4.0, "Dashed from {start} to {end}");
+    assert!((end.y - start.y).abs() < 1.0, "Fell from {start} to {end} while dashing");
+    assert_eq!(harness.gravity_scale(), 1.0);
+}
+
+#[test]
+fn air_dash_is_refreshed_by_landing() {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+    harness.lift_player(48.0);
+    harness.tick(1);
+
+    harness.press(KeyCode::ShiftLeft);
+    harness.tick(1);
+    assert!(harness.player_mut::<DashComponent>().dashing());
+    assert!(!harness.player_mut::<DashComponent>().available);
+
+    harness.release(KeyCode::ShiftLeft);
+    harness.tick_until(600, |harness| harness.jump().on_ground);
+    harness.tick(1);
+    assert!(harness.player_mut::<DashComponent>().available);
+}
+
+#[test]
+fn attaching_the_grapple_refreshes_the_air_dash() {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+    let tuning = MovementTuning::default();
+    let cooldown_ticks = (tuning.dash_cooldown / FRAME_TIME).ceil() as usize;
+
+    // High in the open air right of the spawn, with nothing to bump into for a while
+    harness.player_mut::<Transform>().translation = Vec3::new(200.0, 176.0, 0.0);
+    *harness.player_mut::<Velocity>() = Velocity::zero();
+    harness.play(&[Action::Dash, Action::MoveRight], None);
+    assert!(harness.player_mut::<DashComponent>().dashing());
+
+    while harness.player_mut::<DashComponent>().dashing() {
+        harness.play(&[], None);
+    }
+    harness.play(&[], None);
+    for _ in 0..cooldown_ticks {
+        harness.play(&[], None);
+    }
+    harness.play(&[Action::Dash], None);
+    assert!(!harness.jump().on_ground);
+    assert!(!harness.player_mut::<DashComponent>().dashing(), "The air dash was used up");
+
+    let hook = harness.transform().translation.xy() + Vec2::new(0.0, 12.0);
+    let transform = Transform::from_translation(hook.extend(0.0));
+    harness.app.world.spawn((Hook, TransformBundle { local: transform, global: GlobalTransform::from(transform) }));
+    harness.play(&[Action::Grapple], Some(hook));
+    assert!(harness.player_mut::<DashComponent>().available);
+
+    harness.play(&[Action::Grapple, Action::Dash, Action::MoveRight], None);
+    assert!(harness.player_mut::<DashComponent>().dashing());
+}
+
+#[test]
+fn dash_does_nothing_during_the_cooldown() {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+    let tuning = MovementTuning::default();
+
+    harness.press(KeyCode::ShiftLeft);
+    harness.tick(1);
+    assert!(harness.player_mut::<DashComponent>().dashing());
+    harness.tick_until(60, |harness| !harness.player_mut::<DashComponent>().dashing());
+    harness.release(KeyCode::ShiftLeft);
+    harness.tick(1);
+    let cooldown_until = harness.player_mut::<DashComponent>().cooldown_until.expect("The dash should be cooling down");
+
+    harness.press(KeyCode::ShiftLeft);
+    harness.tick(1);
+    assert!(harness.app.world.resource::<Time<Fixed>>().elapsed_seconds() < cooldown_until);
+    assert!(harness.player_mut::<DashComponent>().available, "Standing on the ground keeps the dash available");
+    assert!(!harness.player_mut::<DashComponent>().dashing());
+
+    harness.release(KeyCode::ShiftLeft);
+    harness.tick((tuning.dash_cooldown / FRAME_TIME).ceil() as usize);
+    harness.press(KeyCode::ShiftLeft);
+    harness.tick(1);
+    assert!(harness.player_mut::<DashComponent>().dashing());
+}
+
+#[test]
+fn dashing_during_a_jump_ends_the_low_gravity_phase() {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+
+    harness.press(KeyCode::Space);
+    harness.tick(3);
+    assert!(harness.jump().jumping);
+
+    harness.press(KeyCode::ShiftLeft);
+    harness.tick(1);
+    assert!(harness.player_mut::<DashComponent>().dashing());
+    harness.tick_until(60, |harness| !harness.player_mut::<DashComponent>().dashing());
+
+    let jump = harness.jump();
+    assert!(!jump.jumping);
+    assert!(jump.falling);
+    assert_eq!(harness.gravity_scale(), 1.0);
+}
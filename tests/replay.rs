@@ -0,0 +1,161 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use bevygame::{Action, DashComponent, Health, JumpComponent, MovementTuning, Replay, ReplayError, ReplayPlayback, ReplayRecorder, ReplayStartState, TickInput, WallContact};
+use common::{TestHarness, FIRST_LEVEL_IID};
+
+fn input(actions: &[Action], aim: Option<Vec2>) -> TickInput {
+    TickInput {
+        buttons: actions.iter().fold(0, |buttons, action| buttons | action.bit()),
+        aim
+    }
+}
+
+fn sample_start_state() -> ReplayStartState {
+    let jump = JumpComponent { last_tried_to_jump: Some(9.9), last_wall_contact: WallContact::Left, ..default() };
+    let dash = DashComponent { available: false, cooldown_until: Some(10.5), facing: -1.0, ..default() };
+    let health = Health { current: 1, invulnerable_until: Some(10.75), ..default() };
+    ReplayStartState::capture(&jump, &GravityScale(1.0), &dash, &health, 10.0)
+}
+
+fn sample_replay() -> Replay {
+    let mut inputs = vec![TickInput::default(); 30];
+    inputs.extend(vec![input(&[Action::MoveRight], None); 120]);
+    inputs.push(input(&[Action::MoveRight, Action::Grapple], Some(Vec2::new(120.5, -40.25))));
+    inputs.extend(vec![input(&[Action::MoveRight, Action::Grapple, Action::ReelIn], None); 45]);
+
+    Replay {
+        level: FIRST_LEVEL_IID.to_string(),
+        tick_rate: 60.0,
+        start_position: Vec2::new(10.0, 20.5),
+        start_velocity: Vec2::new(-1.5, 0.0),
+        start_state: sample_start_state(),
+        tuning: MovementTuning { run_speed: 120.0, ..default() },
+        inputs
+    }
+}
+
+#[test]
+fn replay_survives_a_byte_round_trip() {
+    let replay = sample_replay();
+    assert_eq!(Replay::from_bytes(&replay.to_bytes()).unwrap(), replay);
+}
+
+#[test]
+fn repeated_inputs_are_run_length_encoded() {
+    let replay = sample_replay();
+    let bytes = replay.to_bytes();
+    assert!(bytes.len() < 150, "196 ticks in 4 runs took {} bytes", bytes.len());
+}
+
+#[test]
+fn broken_replays_are_rejected() {
+    let bytes = sample_replay().to_bytes();
+
+    assert!(matches!(Replay::from_bytes(b"not a replay"), Err(ReplayError::BadMagic)));
+    assert!(matches!(Replay::from_bytes(&bytes[..bytes.len() - 3]), Err(ReplayError::Truncated)));
+
+    let mut newer = bytes.clone();
+    newer[4] += 1;
+    assert!(matches!(Replay::from_bytes(&newer), Err(ReplayError::UnsupportedVersion(_))));
+}
+
+/// Header of the sample replay up to the run count, followed by the given runs
+fn with_runs(run_count: u64, runs: &[u8]) -> Vec<u8> {
+    let mut bytes = Replay { inputs: Vec::new(), ..sample_replay() }.to_bytes();
+    // No inputs encode as a single zero run count
+    bytes.pop();
+    write_varint(&mut bytes, run_count);
+    bytes.extend_from_slice(runs);
+    bytes
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+#[test]
+fn replays_claiming_huge_counts_are_rejected() {
+    assert!(matches!(Replay::from_bytes(&with_runs(u64::MAX, &[1, 0, 0, 0])), Err(ReplayError::Truncated)));
+
+    let mut huge_run = Vec::new();
+    write_varint(&mut huge_run, u64::MAX);
+    huge_run.extend_from_slice(&[0, 0, 0]);
+    assert!(matches!(Replay::from_bytes(&with_runs(1, &huge_run)), Err(ReplayError::Corrupted(_))));
+}
+
+/// Runs the scripted input, returning the replay and the player's position after every recorded tick
+fn record_run() -> (Replay, Vec<Vec2>) {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+    harness.app.insert_resource(ReplayRecorder::new(std::env::temp_dir()));
+
+    let mut positions = Vec::new();
+    let mut tick = |harness: &mut TestHarness, frames: usize| {
+        for _ in 0..frames {
+            harness.tick(1);
+            if harness.app.world.resource::<ReplayRecorder>().replay.is_some() {
+                let position = harness.transform().translation.xy();
+                positions.push(position);
+            }
+        }
+    };
+
+    harness.press(KeyCode::KeyD);
+    tick(&mut harness, 40);
+    harness.press(KeyCode::Space);
+    tick(&mut harness, 20);
+    harness.release(KeyCode::Space);
+    harness.release(KeyCode::KeyD);
+    harness.press(KeyCode::KeyA);
+    tick(&mut harness, 50);
+    harness.release(KeyCode::KeyA);
+    tick(&mut harness, 30);
+
+    let replay = harness.app.world.resource::<ReplayRecorder>().replay.clone().expect("Recording should have started");
+    (replay, positions)
+}
+
+#[test]
+fn playback_reproduces_the_recorded_run() {
+    let (replay, recorded_positions) = record_run();
+    assert_eq!(replay.inputs.len(), recorded_positions.len());
+
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+    harness.app.insert_resource(ReplayPlayback::new(replay, false));
+
+    harness.tick_until(100, |harness| harness.app.world.resource::<ReplayPlayback>().tick.is_some());
+    let first_position = harness.transform().translation.xy();
+
+    for (tick, recorded) in recorded_positions.iter().enumerate() {
+        let position = if tick == 0 {
+            first_position
+        } else {
+            harness.tick(1);
+            harness.transform().translation.xy()
+        };
+        assert_eq!(position, *recorded, "Tick {tick}");
+    }
+}
+
+#[test]
+fn playback_restores_the_start_state() {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+    harness.app.insert_resource(ReplayPlayback::new(sample_replay(), false));
+
+    harness.tick_until(100, |harness| harness.app.world.resource::<ReplayPlayback>().tick.is_some());
+    let now = harness.app.world.resource::<Time<Fixed>>().elapsed_seconds();
+
+    let health = harness.player_mut::<Health>().clone();
+    assert_eq!(health.current, 1);
+    assert!(health.invulnerable(now));
+
+    let dash = harness.player_mut::<DashComponent>().clone();
+    assert_eq!(dash.facing, -1.0);
+    assert!(dash.cooldown_until.is_some_and(|until| (until - now - 0.5).abs() < 0.01));
+    assert_eq!(harness.jump().last_wall_contact, WallContact::Left);
+}
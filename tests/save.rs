@@ -0,0 +1,133 @@
+mod common;
+
+use bevy_ecs_ldtk::prelude::*;
+use bevy::prelude::*;
+use bevygame::{load_save, migrate_save, write_save_atomically, ActiveCheckpoint, CheckpointLocation, Damage, GameState, LevelCompleteAction, SaveData, SaveError, SavePlugin, SAVE_VERSION};
+use common::{TestHarness, FIRST_LEVEL_IID};
+
+/// The level right of the first one
+const SECOND_LEVEL_IID : &str = "fe96b930-b0a0-11ee-bd40-ab8946e60376";
+
+fn temporary_save_path(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("bevygame-test-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir.join("save.ron")
+}
+
+#[test]
+fn completing_a_level_keeps_the_best_time() {
+    let mut save = SaveData::default();
+    let level = LevelIid::new("level");
+
+    save.complete_level(&level, 12.0);
+    save.complete_level(&level, 15.0);
+    save.complete_level(&level, 9.5);
+
+    assert!(save.completed_levels.contains("level"));
+    assert_eq!(save.best_times["level"], 9.5);
+}
+
+#[test]
+fn levels_unlock_after_completing_the_previous_one() {
+    let levels = [LevelIid::new("a"), LevelIid::new("b"), LevelIid::new("c")];
+    let mut save = SaveData::default();
+
+    assert!(save.is_unlocked(&levels, 0));
+    assert!(!save.is_unlocked(&levels, 1));
+
+    save.complete_level(&levels[0], 10.0);
+    assert!(save.is_unlocked(&levels, 1));
+    assert!(!save.is_unlocked(&levels, 2));
+}
+
+#[test]
+fn saves_survive_a_write_and_load() {
+    let path = temporary_save_path("round-trip");
+    let mut save = SaveData::default();
+    save.complete_level(&LevelIid::new("level"), 20.0);
+    save.deaths.insert("level".to_string(), 3);
+    save.last_level = Some("level".to_string());
+    save.settings.level_complete_action = LevelCompleteAction::LevelSelect;
+    save.active_checkpoint = Some(CheckpointLocation { level: "level".to_string(), iid: "checkpoint".to_string(), position: Vec2::new(152.0, 40.0) });
+
+    write_save_atomically(&path, &save).unwrap();
+
+    assert_eq!(load_save(&path), save);
+    assert!(!path.with_extension("ron.tmp").exists());
+}
+
+#[test]
+fn missing_or_broken_saves_start_a_new_game() {
+    let path = temporary_save_path("broken");
+    assert_eq!(load_save(&path), SaveData::default());
+
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, "not a save").unwrap();
+    assert_eq!(load_save(&path), SaveData::default());
+    // The broken file is kept for recovering it by hand
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "not a save");
+}
+
+#[test]
+fn saves_from_newer_versions_are_rejected() {
+    let text = format!("(version: {})", SAVE_VERSION + 1);
+
+    assert!(matches!(migrate_save(&text), Err(SaveError::TooNew(_))));
+}
+
+#[test]
+fn version_1_saves_are_migrated_without_a_checkpoint() {
+    let text = r#"(
+        version: 1,
+        completed_levels: ["level"],
+        best_times: {"level": 12.5},
+        deaths: {"level": 2},
+        last_level: Some("level"),
+        settings: (level_complete_action: LevelSelect),
+    )"#;
+
+    let save = migrate_save(text).unwrap();
+
+    assert_eq!(save.version, SAVE_VERSION);
+    assert!(save.completed_levels.contains("level"));
+    assert_eq!(save.best_times["level"], 12.5);
+    assert_eq!(save.deaths["level"], 2);
+    assert_eq!(save.last_level.as_deref(), Some("level"));
+    assert_eq!(save.settings.level_complete_action, LevelCompleteAction::LevelSelect);
+    assert_eq!(save.active_checkpoint, None);
+}
+
+fn set_state(harness: &mut TestHarness, state: GameState) {
+    harness.app.world.resource_mut::<NextState<GameState>>().set(state);
+    harness.tick(1);
+}
+
+#[test]
+fn resuming_goes_to_the_last_level_and_keeps_a_checkpoint_in_another_one() {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+    harness.app.add_plugins(SavePlugin { save_path: None });
+    set_state(&mut harness, GameState::MainMenu);
+    harness.tick(1);
+
+    let checkpoint = CheckpointLocation {
+        level: FIRST_LEVEL_IID.to_string(),
+        iid: "3d1f7a20-5c2e-11ef-9b7c-2f6e1c8a4d11".to_string(),
+        position: Vec2::new(152.0, 40.0)
+    };
+    let mut save = SaveData::default();
+    save.last_level = Some(SECOND_LEVEL_IID.to_string());
+    save.active_checkpoint = Some(checkpoint.clone());
+    harness.app.insert_resource(save);
+
+    set_state(&mut harness, GameState::Playing);
+    harness.wait_until_on_ground();
+    // The second level starts where the first one ends
+    harness.tick_until(60, |harness| harness.transform().translation.x > 512.0);
+    assert_eq!(harness.app.world.resource::<ActiveCheckpoint>().0, Some(checkpoint.clone()));
+
+    // Dying there goes back to the checkpoint
+    let player = harness.player();
+    harness.app.world.send_event(Damage { target: player, source: player, amount: 1, knockback: Vec2::Y });
+    harness.tick_until(10, TestHarness::dead);
+    harness.tick_until(300, |harness| !harness.dead() && (harness.transform().translation.x - checkpoint.position.x).abs() < 1.0);
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use bevygame::Spring;
+use common::*;
+
+#[test]
+fn spring_replaces_the_velocity_along_its_direction() {
+    let spring = Spring::default();
+    assert_eq!(spring.launch(Vec2::new(40.0, -120.0)), Vec2::new(40.0, spring.strength));
+}
+
+#[test]
+fn additive_spring_adds_to_the_velocity() {
+    let spring = Spring { direction: Vec2::X, strength: 100.0, additive: true, ..default() };
+    assert_eq!(spring.launch(Vec2::new(40.0, -120.0)), Vec2::new(140.0, -120.0));
+}
+
+#[test]
+fn walking_into_the_spring_launches_the_player() {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+
+    // The spring in the first level stands on the ground to the right of the spawn
+    harness.press(KeyCode::KeyD);
+    harness.tick_until(600, |harness| harness.velocity().linvel.y > 200.0);
+
+    let spring = harness.app.world.query::<&Spring>().single(&harness.app.world);
+    assert!(spring.compressed_at.is_some());
+}
+
+#[test]
+fn any_dynamic_body_dropped_on_the_spring_is_launched() {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+
+    let spring_position = harness.app.world.query_filtered::<&GlobalTransform, With<Spring>>()
+        .single(&harness.app.world)
+        .translation();
+    let body = harness.app.world.spawn((
+        TransformBundle::from_transform(Transform::from_translation(spring_position + Vec3::Y * 30.0)),
+        RigidBody::Dynamic,
+        Collider::cuboid(4.0, 4.0),
+        Velocity::zero()
+    )).id();
+
+    harness.tick_until(120, |harness| harness.app.world.get::<Velocity>(body).unwrap().linvel.y > 200.0);
+}
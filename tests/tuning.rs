@@ -0,0 +1,36 @@
+use bevygame::MovementTuning;
+
+#[test]
+fn default_tuning_is_valid() {
+    assert_eq!(MovementTuning::default().validate(), Ok(()));
+}
+
+#[test]
+fn shipped_tuning_file_matches_the_defaults() {
+    let file = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/movement.tuning.ron")).unwrap();
+    let tuning: MovementTuning = ron::from_str(&file).unwrap();
+
+    assert_eq!(tuning.validate(), Ok(()));
+    assert_eq!(tuning, MovementTuning::default());
+}
+
+#[test]
+fn missing_fields_keep_their_defaults() {
+    let tuning: MovementTuning = ron::from_str("(run_speed: 120.0)").unwrap();
+
+    assert_eq!(tuning.run_speed, 120.0);
+    assert_eq!(tuning.jump_velocity, MovementTuning::default().jump_velocity);
+}
+
+#[test]
+fn invalid_values_are_rejected() {
+    let negative_speed = MovementTuning { run_speed: -1.0, ..Default::default() };
+    let too_much_acceleration = MovementTuning { acceleration: 1.5, ..Default::default() };
+    let no_deceleration = MovementTuning { deceleration: 0.0, ..Default::default() };
+    let negative_grace_period = MovementTuning { jump_grace_period: -0.1, ..Default::default() };
+    let infinite_jump = MovementTuning { jump_velocity: f32::INFINITY, ..Default::default() };
+
+    for tuning in [negative_speed, too_much_acceleration, no_deceleration, negative_grace_period, infinite_jump] {
+        assert!(tuning.validate().is_err(), "{tuning:?} should be invalid");
+    }
+}
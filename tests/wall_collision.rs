@@ -0,0 +1,190 @@
+mod common;
+
+use bevy::{prelude::*, utils::HashSet};
+use bevy_ecs_ldtk::{ldtk::LayerInstance, prelude::*};
+use bevy_rapier2d::prelude::*;
+use bevygame::{merge_tiles_into_rects, CollisionLayers, TileMaterial, TileRect, Wall};
+use common::*;
+
+fn tiles(coords: impl IntoIterator<Item = (i32, i32)>) -> HashSet<GridCoords> {
+    coords.into_iter().map(|(x, y)| GridCoords { x, y }).collect()
+}
+
+/// Asserts that the rectangles cover exactly the given tiles and don't overlap
+fn assert_exact_cover(rects: &[TileRect], tiles: &HashSet<GridCoords>) {
+    let mut covered = HashSet::new();
+
+    for rect in rects {
+        assert!(rect.left <= rect.right && rect.bottom <= rect.top, "{rect:?} is inverted");
+
+        for x in rect.left..=rect.right {
+            for y in rect.bottom..=rect.top {
+                let coords = GridCoords { x, y };
+                assert!(tiles.contains(&coords), "{rect:?} covers empty tile {coords:?}");
+                assert!(covered.insert(coords), "{coords:?} is covered by more than one rectangle");
+            }
+        }
+    }
+
+    assert_eq!(covered, *tiles, "not every tile is covered");
+}
+
+#[test]
+fn empty_grid_has_no_rects() {
+    assert!(merge_tiles_into_rects(&HashSet::new(), 8, 8).is_empty());
+}
+
+#[test]
+fn single_tile() {
+    let tiles = tiles([(3, 4)]);
+    let rects = merge_tiles_into_rects(&tiles, 8, 8);
+
+    assert_eq!(rects, vec![TileRect { left: 3, right: 3, top: 4, bottom: 4 }]);
+}
+
+#[test]
+fn full_grid_is_one_rect() {
+    let tiles = tiles((0..8).flat_map(|x| (0..5).map(move |y| (x, y))));
+    let rects = merge_tiles_into_rects(&tiles, 8, 5);
+
+    assert_eq!(rects, vec![TileRect { left: 0, right: 7, top: 4, bottom: 0 }]);
+}
+
+#[test]
+fn checkerboard_cannot_be_merged() {
+    let tiles = tiles((0..8).flat_map(|x| (0..8).map(move |y| (x, y))).filter(|(x, y)| (x + y) % 2 == 0));
+    let rects = merge_tiles_into_rects(&tiles, 8, 8);
+
+    assert_exact_cover(&rects, &tiles);
+    assert_eq!(rects.len(), 32);
+}
+
+#[test]
+fn l_shape_is_two_rects() {
+    let tiles = tiles([(0, 0), (1, 0), (2, 0), (3, 0), (0, 1), (0, 2), (0, 3)]);
+    let rects = merge_tiles_into_rects(&tiles, 8, 8);
+
+    assert_exact_cover(&rects, &tiles);
+    assert_eq!(rects.len(), 2);
+}
+
+#[test]
+fn tiles_touching_right_and_top_edge() {
+    let tiles = tiles([(6, 0), (7, 0), (7, 1), (0, 7), (1, 7), (7, 7)]);
+    let rects = merge_tiles_into_rects(&tiles, 8, 8);
+
+    assert_exact_cover(&rects, &tiles);
+    assert_eq!(rects.len(), 4);
+}
+
+#[test]
+fn column_of_equal_plates_merges_into_one_rect() {
+    let tiles = tiles((2..5).flat_map(|x| (0..8).map(move |y| (x, y))));
+    let rects = merge_tiles_into_rects(&tiles, 8, 8);
+
+    assert_eq!(rects, vec![TileRect { left: 2, right: 4, top: 7, bottom: 0 }]);
+}
+
+#[test]
+fn shifted_plates_start_new_rects() {
+    // A staircase: every row is one tile wider than the one below
+    let tiles = tiles((0..6).flat_map(|y| (0..=y).map(move |x| (x, y))));
+    let rects = merge_tiles_into_rects(&tiles, 8, 8);
+
+    assert_exact_cover(&rects, &tiles);
+    assert_eq!(rects.len(), 6);
+}
+
+#[test]
+fn same_plate_after_a_gap_starts_a_new_rect() {
+    let tiles = tiles([(1, 0), (2, 0), (1, 2), (2, 2)]);
+    let rects = merge_tiles_into_rects(&tiles, 8, 8);
+
+    assert_exact_cover(&rects, &tiles);
+    assert_eq!(rects.len(), 2);
+}
+
+#[test]
+fn tiles_outside_the_grid_are_ignored() {
+    let tiles = tiles([(0, 0), (8, 0), (0, 8), (-1, 0)]);
+    let rects = merge_tiles_into_rects(&tiles, 8, 8);
+
+    assert_eq!(rects, vec![TileRect { left: 0, right: 0, top: 0, bottom: 0 }]);
+}
+
+#[test]
+fn rect_size_and_center_follow_the_grid_size() {
+    let rect = TileRect { left: 1, right: 3, top: 2, bottom: 2 };
+
+    assert_eq!(rect.half_extents(16.0), Vec2::new(24.0, 8.0));
+    assert_eq!(rect.center(16.0), Vec2::new(40.0, 40.0));
+    assert_eq!(rect.half_extents(8.0), Vec2::new(12.0, 4.0));
+    assert_eq!(rect.center(8.0), Vec2::new(20.0, 20.0));
+}
+
+#[test]
+fn both_wall_layers_get_colliders_by_default() {
+    let layers = CollisionLayers::default();
+
+    assert!(layers.contains("Walls"));
+    assert!(layers.contains("Walls2"));
+    assert!(!layers.contains("Walls2_baked"));
+}
+
+/// Center, half extents and material of the wall colliders the point is in
+fn walls_at(harness: &mut TestHarness, point: Vec2) -> Vec<(Vec2, Vec2, TileMaterial)> {
+    harness.app.world.query::<(&GlobalTransform, &Collider, &TileMaterial)>()
+        .iter(&harness.app.world)
+        .filter_map(|(transform, collider, material)| {
+            let center = transform.translation().xy();
+            let half_extents = collider.as_cuboid()?.half_extents();
+            (point - center).abs().cmple(half_extents).all().then_some((center, half_extents, *material))
+        })
+        .collect()
+}
+
+fn first_level(harness: &mut TestHarness) -> Entity {
+    harness.app.world.query::<(Entity, &LevelIid)>()
+        .iter(&harness.app.world)
+        .find(|(_, iid)| iid.get() == FIRST_LEVEL_IID)
+        .map(|(entity, _)| entity)
+        .expect("The first level should be spawned")
+}
+
+#[test]
+fn overlapping_tiles_of_two_layers_stay_separate_colliders() {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+
+    // Walls2 has a row of four dirt tiles above the spawn, in the same cells as stone tiles of Walls
+    let walls = walls_at(&mut harness, Vec2::new(112.0, 104.0));
+    assert_eq!(walls.len(), 2, "{walls:?}");
+    assert!(walls.contains(&(Vec2::new(112.0, 104.0), Vec2::new(32.0, 8.0), TileMaterial::DIRT)), "{walls:?}");
+    assert!(walls.iter().any(|(_, _, material)| *material == TileMaterial::STONE), "{walls:?}");
+}
+
+#[test]
+fn colliders_follow_the_grid_size_and_offset_of_their_layer() {
+    let mut harness = TestHarness::new(FIRST_LEVEL_IID);
+    harness.app.world.resource_mut::<CollisionLayers>().0.push("Fine".to_string());
+
+    // Half the usual grid size and moved right and up, as LDtk's y axis points down
+    let layer = LayerMetadata::from(&LayerInstance {
+        identifier: "Fine".to_string(),
+        grid_size: 8,
+        c_wid: 64,
+        c_hei: 32,
+        px_total_offset_x: 4,
+        px_total_offset_y: -6,
+        ..default()
+    });
+    let level = first_level(&mut harness);
+    let layer = harness.app.world.spawn(layer).set_parent(level).id();
+    // In the open air right of the stone block
+    for x in 20..24 {
+        harness.app.world.spawn((GridCoords::new(x, 18), IntGridCell { value: 1 }, Wall)).set_parent(layer);
+    }
+    harness.tick(2);
+
+    // Tiles 20 to 23 span 160 to 192 and row 18 spans 144 to 152, before the offset
+    assert_eq!(walls_at(&mut harness, Vec2::new(180.0, 154.0)), vec![(Vec2::new(180.0, 154.0), Vec2::new(16.0, 4.0), TileMaterial::DIRT)]);
+}